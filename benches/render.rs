@@ -0,0 +1,87 @@
+// Copyright 2018 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Frame time for a 200x80 grid, comparing frames where only a handful of cells change (so only
+//! their rows are uploaded) with frames where every cell changes.
+//!
+//! Run with `cargo bench`. Frames are rendered with a `HeadlessRenderer`, so each frame also
+//! includes reading the image back from the GPU; that cost is the same in both cases.
+
+use std::time::{Duration, Instant};
+
+use dashing::graphics::drawing::SpriteLayer;
+use dashing::graphics::render::{AdapterSelection, HeadlessRenderer, RenderInterface};
+use dashing::resources::color::Palette;
+use dashing::resources::sprite::SpriteTexture;
+
+const WIDTH: u32 = 200;
+const HEIGHT: u32 = 80;
+const FRAMES: u32 = 200;
+
+/// Render `FRAMES` frames, calling `change` to modify the layer before each one, and return the
+/// mean frame time.
+fn time_frames(
+    renderer: &mut HeadlessRenderer,
+    layer: &mut SpriteLayer,
+    mut change: impl FnMut(&mut SpriteLayer, u32),
+) -> Duration {
+    let start = Instant::now();
+    for frame in 0..FRAMES {
+        change(layer, frame);
+        renderer.update(layer.iter());
+        renderer.render_frame().unwrap();
+    }
+    start.elapsed() / FRAMES
+}
+
+fn main() {
+    // 16x16 blank 8x8 sprites.
+    let sprite_texture = SpriteTexture::new_from_pixels(&[0; 128 * 128], 128, 128, 8, 8, 256)
+        .expect("valid sprite texture");
+    let mut renderer =
+        match HeadlessRenderer::new(WIDTH, HEIGHT, &sprite_texture, AdapterSelection::Default) {
+            Ok(renderer) => renderer,
+            Err(e) => {
+                println!("Skipping benchmark, no GPU available: {:?}", e);
+                return;
+            }
+        };
+    let mut layer = SpriteLayer::new(WIDTH as usize, HEIGHT as usize);
+    for (i, cell) in layer.iter_mut().enumerate() {
+        cell.sprite = (i % 256) as u32;
+    }
+
+    // Warm up, and upload the whole grid once.
+    time_frames(&mut renderer, &mut layer, |_, _| {});
+
+    let unchanged = time_frames(&mut renderer, &mut layer, |_, _| {});
+    let few_cells = time_frames(&mut renderer, &mut layer, |layer, frame| {
+        for i in 0..5 {
+            let x = (frame * 37 + i * 41) % WIDTH;
+            let y = (frame * 13 + i * 17) % HEIGHT;
+            layer[(x as usize, y as usize)].sprite = frame % 256;
+        }
+    });
+    let all_cells = time_frames(&mut renderer, &mut layer, |layer, frame| {
+        let palette = Palette::mono([(frame % 256) as u8, 0, 0]);
+        for cell in layer.iter_mut() {
+            cell.palette = palette;
+        }
+    });
+
+    println!("{}x{} grid, mean of {} frames:", WIDTH, HEIGHT, FRAMES);
+    println!("  no cells changed:  {:?}", unchanged);
+    println!("  5 cells changed:   {:?}", few_cells);
+    println!("  all cells changed: {:?}", all_cells);
+}
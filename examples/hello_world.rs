@@ -1,7 +1,5 @@
 use dashing::input::*;
 use dashing::*;
-use pretty_env_logger;
-use time;
 
 // Screen dimensions in characters.
 const WIDTH: u32 = 21;
@@ -49,7 +47,7 @@ impl Driver for ExampleDriver {
                     ),
                 ),
                 sprite: c as u32,
-                transparent: false,
+                ..Default::default()
             };
         }
         // Update the renderer.
@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+/// Sprite and palette animation.
+pub mod animation;
 /// High-level drawing routines.
 pub mod drawing;
 /// Low-level rendering subsystem.
@@ -0,0 +1,299 @@
+// Copyright 2018 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use crate::resources::color::{Color, Palette};
+
+/// What an animation does after its last frame.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum AnimationMode {
+    /// Start again from the first frame.
+    #[default]
+    Loop,
+    /// Play the frames backwards to the first one, then forwards again, and so on.
+    PingPong,
+    /// Stay on the last frame.
+    Once,
+}
+
+/// Identifies an animation registered with `RenderInterface::add_animation()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AnimationId(pub(crate) u32);
+
+/// A sequence of sprites, each shown for its own duration. Register animations with the renderer
+/// using `RenderInterface::add_animation()`, and play one in a cell by setting
+/// `SpriteCell::animation`. Frames are selected on the GPU, so animated cells don't need to be
+/// touched from the CPU while they play.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Animation {
+    frames: Vec<(u32, Duration)>,
+    mode: AnimationMode,
+}
+
+impl Animation {
+    /// Create an animation with no frames.
+    pub fn new(mode: AnimationMode) -> Self {
+        Animation {
+            frames: vec![],
+            mode,
+        }
+    }
+
+    /// Add a frame that shows `sprite` for `duration`.
+    #[must_use]
+    pub fn with_frame(mut self, sprite: u32, duration: Duration) -> Self {
+        self.frames.push((sprite, duration));
+
+        self
+    }
+
+    /// The animation's frames, in order.
+    pub fn frames(&self) -> &[(u32, Duration)] {
+        &self.frames
+    }
+
+    /// What the animation does after its last frame.
+    pub fn mode(&self) -> AnimationMode {
+        self.mode
+    }
+
+    /// One full cycle of the animation, with ping-pong animations unrolled into the equivalent
+    /// loop, e.g. frames `0, 1, 2` become `0, 1, 2, 1`.
+    pub(crate) fn cycle(&self) -> Vec<(u32, Duration)> {
+        let mut frames = self.frames.clone();
+        if self.mode == AnimationMode::PingPong && frames.len() > 2 {
+            frames.extend(self.frames[1..self.frames.len() - 1].iter().rev());
+        }
+        frames
+    }
+
+    /// The sprite shown `t` after the animation started, or `None` if it has no frames. This is
+    /// how the renderer selects frames, for use on the CPU.
+    pub fn sprite_at(&self, t: Duration) -> Option<u32> {
+        let frames = self.cycle();
+        let total: Duration = frames.iter().map(|&(_, d)| d).sum();
+        let mut t = match self.mode {
+            _ if total.is_zero() => Duration::ZERO,
+            AnimationMode::Once => t,
+            AnimationMode::Loop | AnimationMode::PingPong => {
+                Duration::from_nanos((t.as_nanos() % total.as_nanos()) as u64)
+            }
+        };
+        for &(sprite, duration) in frames.iter() {
+            if t < duration {
+                return Some(sprite);
+            }
+            t -= duration;
+        }
+        frames.last().map(|&(sprite, _)| sprite)
+    }
+}
+
+/// Identifies a palette cycle registered with `RenderInterface::add_palette_cycle()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PaletteCycleId(pub(crate) u32);
+
+/// A change to a cell's palette over time, for classic color-cycling effects like flowing water
+/// or blinking lights. Register palette cycles with the renderer using
+/// `RenderInterface::add_palette_cycle()`, and apply one to a cell by setting
+/// `SpriteCell::palette_cycle`. Cycles are evaluated on the GPU from the renderer's elapsed time,
+/// so every cell using the same cycle stays in step and none of them need to be touched from the
+/// CPU.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PaletteCycle {
+    /// Rotate the colors of palette indices `first..first + count`: every `step`, each index in
+    /// the range takes the color of the one after it, and the last takes the first's.
+    Rotate {
+        /// First palette index in the range.
+        first: usize,
+        /// Number of palette indices in the range.
+        count: usize,
+        /// Time between steps.
+        step: Duration,
+    },
+    /// Switch between the cell's own palette and `palette`, spending `period` on each.
+    Blink {
+        /// Palette shown every other period.
+        palette: Palette,
+        /// Time spent on each palette.
+        period: Duration,
+    },
+    /// Fade palette index `index` smoothly through `colors`, from the last back to the first, and
+    /// so on.
+    Gradient {
+        /// Palette index to change.
+        index: usize,
+        /// Colors to fade between.
+        colors: Vec<Color>,
+        /// Time taken to go through all of the colors.
+        period: Duration,
+    },
+}
+
+impl PaletteCycle {
+    /// The palette a cell with palette `palette` shows `t` after the renderer started. This is
+    /// how the renderer evaluates palette cycles, for use on the CPU.
+    pub fn palette_at(&self, palette: Palette, t: Duration) -> Palette {
+        // Number of whole periods that have passed.
+        let steps = |period: Duration| {
+            if period.is_zero() {
+                0
+            } else {
+                (t.as_nanos() / period.as_nanos()) as usize
+            }
+        };
+        match self {
+            PaletteCycle::Rotate { first, count, step } => {
+                let first = (*first).min(16);
+                let count = (*count).min(16 - first);
+                if count == 0 {
+                    return palette;
+                }
+                let shift = steps(*step) % count;
+                let mut out = palette;
+                for i in 0..count {
+                    out = out.set(first + i, palette[first + (i + shift) % count]);
+                }
+                out
+            }
+            PaletteCycle::Blink {
+                palette: other,
+                period,
+            } => {
+                if steps(*period) % 2 == 1 {
+                    *other
+                } else {
+                    palette
+                }
+            }
+            PaletteCycle::Gradient {
+                index,
+                colors,
+                period,
+            } => {
+                if colors.is_empty() || *index >= 16 {
+                    return palette;
+                }
+                let phase = if period.is_zero() {
+                    0.0
+                } else {
+                    (t.as_nanos() % period.as_nanos()) as f64 / period.as_nanos() as f64
+                };
+                let position = phase * colors.len() as f64;
+                let k = position as usize % colors.len();
+                let f = position.fract();
+                let from: [u8; 3] = colors[k].into();
+                let to: [u8; 3] = colors[(k + 1) % colors.len()].into();
+                let mut color = [0; 3];
+                for c in 0..3 {
+                    color[c] = (from[c] as f64 * (1.0 - f) + to[c] as f64 * f).round() as u8;
+                }
+                palette.set(*index, color)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn animation(mode: AnimationMode) -> Animation {
+        Animation::new(mode)
+            .with_frame(1, Duration::from_millis(100))
+            .with_frame(2, Duration::from_millis(200))
+            .with_frame(3, Duration::from_millis(100))
+    }
+
+    fn sprites(animation: &Animation) -> Vec<u32> {
+        (0..12)
+            .map(|i| {
+                animation
+                    .sprite_at(Duration::from_millis(i * 100 + 50))
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn modes() {
+        assert_eq!(
+            sprites(&animation(AnimationMode::Loop)),
+            [1, 2, 2, 3, 1, 2, 2, 3, 1, 2, 2, 3]
+        );
+        assert_eq!(
+            sprites(&animation(AnimationMode::PingPong)),
+            [1, 2, 2, 3, 2, 2, 1, 2, 2, 3, 2, 2]
+        );
+        assert_eq!(
+            sprites(&animation(AnimationMode::Once)),
+            [1, 2, 2, 3, 3, 3, 3, 3, 3, 3, 3, 3]
+        );
+    }
+
+    #[test]
+    fn palette_cycles() {
+        let palette = Palette::default();
+        let second = Duration::from_secs(1);
+
+        let rotate = PaletteCycle::Rotate {
+            first: 1,
+            count: 3,
+            step: second,
+        };
+        let rotated = rotate.palette_at(palette, Duration::from_millis(1500));
+        assert_eq!(
+            [rotated[0], rotated[1], rotated[2], rotated[3], rotated[4]],
+            [palette[0], palette[2], palette[3], palette[1], palette[4]]
+        );
+        assert_eq!(rotate.palette_at(palette, 3 * second), palette);
+
+        let other = Palette::mono([1, 2, 3]);
+        let blink = PaletteCycle::Blink {
+            palette: other,
+            period: second,
+        };
+        assert_eq!(
+            blink.palette_at(palette, Duration::from_millis(500)),
+            palette
+        );
+        assert_eq!(
+            blink.palette_at(palette, Duration::from_millis(1500)),
+            other
+        );
+
+        let gradient = PaletteCycle::Gradient {
+            index: 15,
+            colors: vec![Color::new(0, 0, 0), Color::new(200, 100, 0)],
+            period: 2 * second,
+        };
+        assert_eq!(
+            gradient.palette_at(palette, Duration::from_millis(500))[15],
+            [100, 50, 0]
+        );
+        assert_eq!(
+            gradient.palette_at(palette, Duration::from_millis(1500))[15],
+            [100, 50, 0]
+        );
+        assert_eq!(gradient.palette_at(palette, 2 * second)[15], [0, 0, 0]);
+    }
+
+    #[test]
+    fn degenerate() {
+        assert_eq!(Animation::default().sprite_at(Duration::ZERO), None);
+        let still = Animation::new(AnimationMode::Loop).with_frame(7, Duration::ZERO);
+        assert_eq!(still.sprite_at(Duration::from_secs(3)), Some(7));
+    }
+}
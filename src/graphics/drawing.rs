@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::graphics::animation::{AnimationId, PaletteCycleId};
 use crate::resources::color::Palette;
 use crate::resources::sprite::SpriteMap;
 
@@ -24,6 +25,43 @@ pub struct SpriteCell {
     pub sprite: u32,
     /// Transparency.
     pub transparent: bool,
+    /// Bitmask of palette indices that should be treated as transparent (bit `i` corresponds to
+    /// palette index `i`). This can be used to key out e.g. the background color of a sprite so
+    /// the layer below shows through. Ignored by `SpriteLayer::stamp_onto`.
+    pub transparent_mask: u16,
+    /// Arbitrary data passed through to the cell shader (see `WindowBuilder::with_cell_shader()`),
+    /// e.g. to select an animation or give each cell a random phase. Ignored by the default
+    /// shader and by renderers without cell shaders.
+    pub user_data: u32,
+    /// Animation to play in the cell, from `RenderInterface::add_animation()`. While set, it
+    /// replaces `sprite`, except on the terminal renderer, which doesn't support animation. The
+    /// animation restarts whenever this changes.
+    pub animation: Option<AnimationId>,
+    /// Palette cycle applied to `palette`, from `RenderInterface::add_palette_cycle()`. Ignored
+    /// by renderers without palette cycling.
+    pub palette_cycle: Option<PaletteCycleId>,
+}
+
+impl SpriteCell {
+    /// Mark the given palette index as transparent. Panics if `i` is not a palette index, i.e.
+    /// not less than 16.
+    #[must_use]
+    pub fn with_transparent_index(mut self, i: usize) -> Self {
+        assert!(
+            i < 16,
+            "palette index {} out of range (must be less than 16)",
+            i
+        );
+        self.transparent_mask |= 1 << i;
+        self
+    }
+
+    /// Returns `true` iff the given palette index is transparent in this cell, either because the
+    /// whole cell is transparent or because the index is keyed out by `transparent_mask`. Indices
+    /// past the end of the palette are only transparent if the whole cell is.
+    pub fn is_transparent_at(&self, i: usize) -> bool {
+        self.transparent || (i < 16 && (self.transparent_mask >> i) & 1 == 1)
+    }
 }
 
 /// A 2D array of sprite cells.
@@ -45,12 +83,12 @@ impl SpriteLayer {
     }
 
     /// Get an iterator over all of the cells in the layer.
-    pub fn iter(&self) -> std::slice::Iter<SpriteCell> {
+    pub fn iter(&self) -> std::slice::Iter<'_, SpriteCell> {
         self.data.iter()
     }
 
     /// Get a mutable iterator over all of the cells in the layer.
-    pub fn iter_mut(&mut self) -> std::slice::IterMut<SpriteCell> {
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, SpriteCell> {
         self.data.iter_mut()
     }
 
@@ -98,6 +136,20 @@ impl SpriteLayer {
         }
     }
 
+    /// Change the size of the layer. Cells that are still within bounds keep their contents, and
+    /// new cells are set to the default.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        let mut data = vec![SpriteCell::default(); width * height].into_boxed_slice();
+        for y in 0..height.min(self.height) {
+            for x in 0..width.min(self.width) {
+                data[y * width + x] = self[(x, y)];
+            }
+        }
+        self.width = width;
+        self.height = height;
+        self.data = data;
+    }
+
     /// Get width of the layer.
     pub fn width(&self) -> usize {
         self.width
@@ -269,6 +321,19 @@ mod tests {
         );
     }
     #[test]
+    fn resize() {
+        let mut l = SpriteLayer::new(2, 2);
+        for (i, cell) in l.iter_mut().enumerate() {
+            cell.sprite = i as u32 + 1;
+        }
+        l.resize(3, 1);
+        assert_eq!((l.width(), l.height()), (3, 1));
+        assert_eq!(
+            l.iter().map(|c| c.sprite).collect::<Vec<u32>>(),
+            vec![1, 2, 0]
+        );
+    }
+    #[test]
     fn stamp_transparency() {
         let mut l1 = SpriteLayer::new(4, 4);
         let mut l2 = SpriteLayer::new(2, 3);
@@ -291,4 +356,27 @@ mod tests {
             expected_sprites
         );
     }
+    #[test]
+    fn transparent_index() {
+        let cell = SpriteCell::default()
+            .with_transparent_index(0)
+            .with_transparent_index(15);
+        assert_eq!(cell.transparent_mask, 0x8001);
+        assert!(cell.is_transparent_at(0));
+        assert!(!cell.is_transparent_at(1));
+        assert!(cell.is_transparent_at(15));
+        assert!(!cell.is_transparent_at(16));
+
+        let cell = SpriteCell {
+            transparent: true,
+            ..Default::default()
+        };
+        assert!((0..16).all(|i| cell.is_transparent_at(i)));
+        assert!(cell.is_transparent_at(16));
+    }
+    #[test]
+    #[should_panic(expected = "palette index 16 out of range")]
+    fn transparent_index_out_of_range() {
+        let _ = SpriteCell::default().with_transparent_index(16);
+    }
 }
@@ -16,18 +16,37 @@
 use itertools::Itertools;
 use log::{info, trace};
 
+use crate::graphics::animation::{
+    Animation, AnimationId, AnimationMode, PaletteCycle, PaletteCycleId,
+};
 use crate::graphics::drawing::SpriteCell;
+use crate::resources::color::Color;
 use crate::resources::sprite::SpriteTexture;
+use crate::window::ScalingMode;
 use wgpu::util::DeviceExt;
 
+mod effects;
+mod headless;
+mod post_process;
+mod software;
+mod stats;
 #[cfg(test)]
 mod tests;
 
+pub use self::effects::{ColorGrading, Effect};
+pub use self::headless::{AdapterSelection, HeadlessRenderer};
+pub use self::post_process::PostProcessPass;
+pub use self::software::SoftwareRenderer;
+pub use self::stats::{FrameStats, TimingHistory, FRAME_HISTORY};
+
 /// Error type for the renderer.
 #[derive(Debug)]
 pub enum RenderError {
     /// Generic error.
     GeneralError(String),
+    /// A screen shader or post-processing pass failed to compile, or doesn't match the bindings
+    /// the renderer provides.
+    ShaderError(String),
 }
 
 impl<S> std::convert::From<S> for RenderError
@@ -47,6 +66,26 @@ struct CellGlobals {
     sprite_texture_dimensions: [u32; 2],
     sprite_dimensions: [u32; 2],
     palette_texture_dimensions: [u32; 2],
+    elapsed_time: f32,
+    frame_counter: u32,
+}
+
+impl CellGlobals {
+    /// Update the sprite and sprite map dimensions to match `sprite_texture`.
+    fn set_sprite_texture(&mut self, sprite_texture: &SpriteTexture) {
+        self.sprite_map_dimensions = [
+            (sprite_texture.width() / sprite_texture.sprite_width()) as u32,
+            (sprite_texture.height() / sprite_texture.sprite_height()) as u32,
+        ];
+        self.sprite_texture_dimensions = [
+            sprite_texture.width() as u32,
+            sprite_texture.height() as u32,
+        ];
+        self.sprite_dimensions = [
+            sprite_texture.sprite_width() as u32,
+            sprite_texture.sprite_height() as u32,
+        ];
+    }
 }
 
 #[repr(C)]
@@ -56,6 +95,120 @@ struct ScreenGlobals {
     scale_factor: [f32; 2],
     frame_counter: u32,
     elapsed_time: f32,
+    translate: [f32; 2],
+    uv_scale: [f32; 2],
+    uv_offset: [f32; 2],
+    viewport: [f32; 4],
+    source_size: [f32; 2],
+    _padding: [f32; 2],
+}
+
+// An animation registered with `RenderInterface::add_animation()`, as stored on the GPU. Its
+// frames are `frame_count` consecutive `AnimationFrame`s starting at `first_frame`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct AnimationInfo {
+    first_frame: u32,
+    frame_count: u32,
+    once: u32,
+    duration: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct AnimationFrame {
+    sprite: u32,
+    duration: f32,
+}
+
+// A palette cycle registered with `RenderInterface::add_palette_cycle()`, as stored on the GPU.
+// See `PaletteCycle` in `cell_common.wgsl` for how the fields are used by each kind of cycle.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct PaletteCycleInfo {
+    kind: u32,
+    first: u32,
+    count: u32,
+    period: f32,
+    first_color: u32,
+    color_count: u32,
+}
+
+/// Animations and palette cycles registered with the renderer, in the layout the cell shaders
+/// read them in.
+#[derive(Default)]
+struct AnimationTables {
+    animations: Vec<AnimationInfo>,
+    animation_frames: Vec<AnimationFrame>,
+    palette_cycles: Vec<PaletteCycleInfo>,
+    palette_cycle_colors: Vec<[f32; 4]>,
+}
+
+impl AnimationTables {
+    fn add_animation(&mut self, animation: &Animation) -> AnimationId {
+        let frames = animation.cycle();
+        self.animations.push(AnimationInfo {
+            first_frame: self.animation_frames.len() as u32,
+            frame_count: frames.len() as u32,
+            once: (animation.mode() == AnimationMode::Once) as u32,
+            duration: frames.iter().map(|(_, d)| d.as_secs_f32()).sum(),
+        });
+        self.animation_frames
+            .extend(frames.iter().map(|&(sprite, duration)| AnimationFrame {
+                sprite,
+                duration: duration.as_secs_f32(),
+            }));
+
+        AnimationId(self.animations.len() as u32 - 1)
+    }
+
+    fn add_palette_cycle(&mut self, cycle: &PaletteCycle) -> PaletteCycleId {
+        let first_color = self.palette_cycle_colors.len() as u32;
+        let info = match cycle {
+            PaletteCycle::Rotate { first, count, step } => {
+                let first = (*first).min(16);
+                PaletteCycleInfo {
+                    kind: 0,
+                    first: first as u32,
+                    count: (*count).min(16 - first) as u32,
+                    period: step.as_secs_f32(),
+                    ..Default::default()
+                }
+            }
+            PaletteCycle::Blink { palette, period } => {
+                let colors: [[u8; 3]; 16] = (*palette).into();
+                self.palette_cycle_colors
+                    .extend(colors.iter().map(|&c| <[f32; 4]>::from(Color::from(c))));
+                PaletteCycleInfo {
+                    kind: 1,
+                    period: period.as_secs_f32(),
+                    first_color,
+                    color_count: 16,
+                    ..Default::default()
+                }
+            }
+            PaletteCycle::Gradient {
+                index,
+                colors,
+                period,
+            } => {
+                self.palette_cycle_colors
+                    .extend(colors.iter().map(|&c| <[f32; 4]>::from(c)));
+                PaletteCycleInfo {
+                    kind: 2,
+                    // Out-of-range indices never match a palette index, so they have no effect.
+                    first: (*index).min(16) as u32,
+                    period: period.as_secs_f32(),
+                    first_color,
+                    color_count: colors.len() as u32,
+                    ..Default::default()
+                }
+            }
+        };
+        self.palette_cycles.push(info);
+
+        PaletteCycleId(self.palette_cycles.len() as u32 - 1)
+    }
 }
 
 #[repr(C)]
@@ -87,12 +240,21 @@ impl Vertex {
 }
 
 #[repr(C)]
-#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
 struct Instance {
     translate: [f32; 2],
     cell_coords: [u32; 2],
     sprite: u32,
     index: u32,
+    // Bitmask of transparent palette indices; 0xFFFF means the whole cell is transparent.
+    transparent_mask: u32,
+    user_data: u32,
+    // One more than the `AnimationId`, or 0 if the cell isn't animated.
+    animation: u32,
+    // Elapsed time in seconds when the cell's animation was set.
+    animation_start: f32,
+    // One more than the `PaletteCycleId`, or 0 if the cell's palette doesn't change.
+    palette_cycle: u32,
 }
 
 impl Instance {
@@ -125,6 +287,46 @@ impl Instance {
                     shader_location: 5,
                     format: wgpu::VertexFormat::Uint32,
                 },
+                wgpu::VertexAttribute {
+                    offset: (std::mem::size_of::<[f32; 2]>()
+                        + std::mem::size_of::<[u32; 2]>()
+                        + std::mem::size_of::<u32>() * 2)
+                        as wgpu::BufferAddress,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+                wgpu::VertexAttribute {
+                    offset: (std::mem::size_of::<[f32; 2]>()
+                        + std::mem::size_of::<[u32; 2]>()
+                        + std::mem::size_of::<u32>() * 3)
+                        as wgpu::BufferAddress,
+                    shader_location: 7,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+                wgpu::VertexAttribute {
+                    offset: (std::mem::size_of::<[f32; 2]>()
+                        + std::mem::size_of::<[u32; 2]>()
+                        + std::mem::size_of::<u32>() * 4)
+                        as wgpu::BufferAddress,
+                    shader_location: 8,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+                wgpu::VertexAttribute {
+                    offset: (std::mem::size_of::<[f32; 2]>()
+                        + std::mem::size_of::<[u32; 2]>()
+                        + std::mem::size_of::<u32>() * 5)
+                        as wgpu::BufferAddress,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                wgpu::VertexAttribute {
+                    offset: (std::mem::size_of::<[f32; 2]>()
+                        + std::mem::size_of::<[u32; 2]>()
+                        + std::mem::size_of::<u32>() * 6)
+                        as wgpu::BufferAddress,
+                    shader_location: 10,
+                    format: wgpu::VertexFormat::Uint32,
+                },
             ],
         }
     }
@@ -170,6 +372,9 @@ const SCREEN_QUAD_VERTICES: [Vertex; 4] = [
     },
 ];
 
+// Value of `Instance::transparent_mask` for a cell that is completely transparent.
+const ALL_TRANSPARENT: u32 = 0xFFFF;
+
 // Triangulation for the above vertices, shared by both the cell quads and the screen quad.
 const QUAD_INDICES: [u16; 6] = [0, 1, 2, 2, 3, 0];
 
@@ -189,6 +394,26 @@ enum RenderOutput {
 }
 
 impl RenderOutput {
+    /// Create an offscreen output texture of the given size.
+    fn new_texture(device: &wgpu::Device, size: wgpu::Extent3d) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            label: Some("final output texture"),
+        });
+
+        let texture_view = texture.create_view(&Default::default());
+        RenderOutput::Texture {
+            texture,
+            texture_view,
+            output_size: size,
+        }
+    }
+
     fn output_size(&self) -> (u32, u32) {
         match self {
             RenderOutput::Surface {
@@ -209,49 +434,705 @@ impl RenderOutput {
     }
 }
 
+/// GPU-side state for one layer of sprite cells. Each layer has its own instance buffer and palette
+/// texture; layers share the sprite texture and the cell pipeline.
+struct Layer {
+    instances: Box<[Instance]>,
+    instance_buffer: wgpu::Buffer,
+
+    // Palette colors for each cell, in the RGBA layout used by the palette texture.
+    palette_data: Box<[[[u8; 4]; 16]]>,
+    palette_texture: wgpu::Texture,
+
+    // Width of the grid, and which of its rows have changed since they were last uploaded.
+    width: usize,
+    dirty_rows: Box<[bool]>,
+
+    texture_bind_group: wgpu::BindGroup,
+}
+
+impl Layer {
+    /// Create a new layer. The base layer is initially opaque white; layers above it start out
+    /// fully transparent so they don't obscure anything until they are updated.
+    fn new(
+        device: &wgpu::Device,
+        dimensions: (u32, u32),
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        sprite_texture_view: &wgpu::TextureView,
+        palette_texture_size: wgpu::Extent3d,
+        opaque: bool,
+    ) -> Self {
+        let mut instances = vec![Instance::default(); (dimensions.0 * dimensions.1) as usize];
+        let palette_data = vec![[[255, 255, 255, 255]; 16]; (dimensions.0 * dimensions.1) as usize];
+
+        for y in 0..dimensions.1 {
+            for x in 0..dimensions.0 {
+                instances[(y * dimensions.0 + x) as usize] = Instance {
+                    translate: [
+                        -1.0 + (x as f32 * 2.0 / dimensions.0 as f32),
+                        1.0 - ((y as f32 + 1.0) * 2.0 / dimensions.1 as f32),
+                    ],
+                    cell_coords: [x as _, y as _],
+                    sprite: 0,
+                    index: y * dimensions.0 + x,
+                    transparent_mask: if opaque { 0 } else { ALL_TRANSPARENT },
+                    user_data: 0,
+                    animation: 0,
+                    animation_start: 0.0,
+                    palette_cycle: 0,
+                };
+            }
+        }
+
+        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Instance buffer"),
+            contents: bytemuck::cast_slice(&instances),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let palette_texture = device.create_texture(&wgpu::TextureDescriptor {
+            size: palette_texture_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            label: Some("palette texture"),
+        });
+
+        let texture_bind_group = Self::create_texture_bind_group(
+            device,
+            texture_bind_group_layout,
+            sprite_texture_view,
+            &palette_texture,
+        );
+
+        Layer {
+            instances: instances.into_boxed_slice(),
+            instance_buffer,
+            palette_data: palette_data.into_boxed_slice(),
+            palette_texture,
+            width: dimensions.0 as usize,
+            // The palette texture starts out uninitialized, so everything needs uploading.
+            dirty_rows: vec![true; dimensions.1 as usize].into_boxed_slice(),
+            texture_bind_group,
+        }
+    }
+
+    fn create_texture_bind_group(
+        device: &wgpu::Device,
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        sprite_texture_view: &wgpu::TextureView,
+        palette_texture: &wgpu::Texture,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: texture_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(sprite_texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(
+                        &palette_texture.create_view(&Default::default()),
+                    ),
+                },
+            ],
+            label: Some("cell_texture_bind_group"),
+        })
+    }
+
+    /// Point the layer at a new sprite texture, keeping its contents.
+    fn set_sprite_texture(
+        &mut self,
+        device: &wgpu::Device,
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        sprite_texture_view: &wgpu::TextureView,
+    ) {
+        self.texture_bind_group = Self::create_texture_bind_group(
+            device,
+            texture_bind_group_layout,
+            sprite_texture_view,
+            &self.palette_texture,
+        );
+    }
+
+    /// Copy cell data into the layer, marking the rows that changed for upload. `now` is the
+    /// renderer's elapsed time in seconds, used to restart the animation of any cell whose
+    /// animation changed.
+    fn update<'a, T, U>(&mut self, data: T, now: f32)
+    where
+        T: Iterator<Item = U>,
+        U: Into<&'a SpriteCell>,
+    {
+        for (n, (i, d, p)) in itertools::multizip((
+            self.instances.iter_mut(),
+            data,
+            self.palette_data.iter_mut(),
+        ))
+        .enumerate()
+        {
+            let c: &SpriteCell = d.into();
+            let mut instance = Instance {
+                sprite: c.sprite,
+                transparent_mask: if c.transparent {
+                    ALL_TRANSPARENT
+                } else {
+                    c.transparent_mask as u32
+                },
+                user_data: c.user_data,
+                palette_cycle: c.palette_cycle.map_or(0, |p| p.0 + 1),
+                ..*i
+            };
+            let animation = c.animation.map_or(0, |a| a.0 + 1);
+            if animation != i.animation {
+                instance.animation = animation;
+                instance.animation_start = now;
+            }
+            let palette = c.palette.into();
+            if instance != *i || palette != *p {
+                *i = instance;
+                *p = palette;
+                self.dirty_rows[n / self.width] = true;
+            }
+        }
+    }
+
+    /// Upload the rows that changed since the last upload to the GPU.
+    fn upload(&mut self, queue: &wgpu::Queue, palette_texture_size: wgpu::Extent3d) {
+        for rows in dirty_ranges(&self.dirty_rows) {
+            let cells = rows.start * self.width..rows.end * self.width;
+            queue.write_buffer(
+                &self.instance_buffer,
+                (cells.start * std::mem::size_of::<Instance>()) as wgpu::BufferAddress,
+                bytemuck::cast_slice(&self.instances[cells.clone()]),
+            );
+
+            // Each row of the grid is one layer of the palette texture.
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &self.palette_texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: rows.start as u32,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                bytemuck::cast_slice(&self.palette_data[cells]),
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(16 * 4),
+                    rows_per_image: std::num::NonZeroU32::new(palette_texture_size.height),
+                },
+                wgpu::Extent3d {
+                    depth_or_array_layers: rows.len() as u32,
+                    ..palette_texture_size
+                },
+            );
+        }
+        self.dirty_rows.fill(false);
+    }
+}
+
+/// Group the set entries of `dirty` into runs of consecutive indices, so each run can be uploaded
+/// with a single write.
+fn dirty_ranges(dirty: &[bool]) -> Vec<std::ops::Range<usize>> {
+    let mut ranges: Vec<std::ops::Range<usize>> = vec![];
+    for (i, _) in dirty.iter().enumerate().filter(|(_, &d)| d) {
+        match ranges.last_mut() {
+            Some(range) if range.end == i => range.end += 1,
+            _ => ranges.push(i..i + 1),
+        }
+    }
+    ranges
+}
+
+/// How often the statistics overlay is redrawn, in frames. Redrawing it every frame would make the
+/// numbers too jittery to read.
+const OVERLAY_REFRESH_FRAMES: u32 = 15;
+
+/// Size of the palette texture for a grid of the given dimensions: one 16-texel row per cell.
+fn palette_texture_size(dimensions: (u32, u32)) -> wgpu::Extent3d {
+    wgpu::Extent3d {
+        width: 16,
+        height: dimensions.0,
+        depth_or_array_layers: dimensions.1,
+    }
+}
+
+/// The area of the window that the rendered grid is scaled into, in physical pixels relative to
+/// the top left of the window. With `ScalingMode::Fill` this can extend past the window's edges.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Viewport {
+    pub(crate) x: i32,
+    pub(crate) y: i32,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+}
+
+impl Viewport {
+    /// Fit an image of size `content` into a window of size `screen`, both in pixels.
+    pub(crate) fn new(screen: (u32, u32), content: (u32, u32), mode: ScalingMode) -> Self {
+        let (sw, sh) = screen;
+        let (cw, ch) = (content.0.max(1), content.1.max(1));
+        let scale_x = sw as f64 / cw as f64;
+        let scale_y = sh as f64 / ch as f64;
+        let (width, height) = match mode {
+            ScalingMode::Fit => {
+                let scale = scale_x.min(scale_y);
+                ((cw as f64 * scale) as u32, (ch as f64 * scale) as u32)
+            }
+            // Fall back to downscaling if the window is smaller than the content.
+            ScalingMode::IntegerFit if sw < cw || sh < ch => {
+                return Viewport::new(screen, content, ScalingMode::Fit)
+            }
+            ScalingMode::IntegerFit => {
+                let scale = (sw / cw).min(sh / ch);
+                (cw * scale, ch * scale)
+            }
+            ScalingMode::Stretch => (sw, sh),
+            ScalingMode::Fill => {
+                let scale = scale_x.max(scale_y);
+                (
+                    (cw as f64 * scale).ceil() as u32,
+                    (ch as f64 * scale).ceil() as u32,
+                )
+            }
+        };
+        Viewport {
+            x: (sw as i32 - width as i32).div_euclid(2),
+            y: (sh as i32 - height as i32).div_euclid(2),
+            width,
+            height,
+        }
+    }
+
+    /// Map a position in the window to the cell under it in a grid of size `dimensions`, or
+    /// `None` if the position is outside the viewport.
+    pub(crate) fn cell_at(
+        &self,
+        position: (f64, f64),
+        dimensions: (u32, u32),
+    ) -> Option<(u32, u32)> {
+        self.uv_at(position).map(|uv| uv_to_cell(uv, dimensions))
+    }
+
+    /// The part of the viewport inside a window of size `screen`, or `None` if there is none.
+    pub(crate) fn visible_part(&self, screen: (u32, u32)) -> Option<Viewport> {
+        let x = self.x.max(0);
+        let y = self.y.max(0);
+        let right = (self.x + self.width as i32).min(screen.0 as i32);
+        let bottom = (self.y + self.height as i32).min(screen.1 as i32);
+        (right > x && bottom > y).then(|| Viewport {
+            x,
+            y,
+            width: (right - x) as u32,
+            height: (bottom - y) as u32,
+        })
+    }
+
+    /// Map a position in the window to texture coordinates within the viewport, in `[0, 1)`, or
+    /// `None` if the position is outside the viewport.
+    pub(crate) fn uv_at(&self, position: (f64, f64)) -> Option<(f64, f64)> {
+        let x = position.0 - self.x as f64;
+        let y = position.1 - self.y as f64;
+        if x < 0.0 || y < 0.0 || x >= self.width as f64 || y >= self.height as f64 {
+            return None;
+        }
+        Some((x / self.width as f64, y / self.height as f64))
+    }
+}
+
+/// Map texture coordinates in `[0, 1)` to a cell in a grid of size `dimensions`.
+fn uv_to_cell((u, v): (f64, f64), dimensions: (u32, u32)) -> (u32, u32) {
+    (
+        ((u * dimensions.0 as f64) as u32).min(dimensions.0 - 1),
+        ((v * dimensions.1 as f64) as u32).min(dimensions.1 - 1),
+    )
+}
+
+/// Zoom and pan applied to the sprite grid as it is drawn to the screen. The camera only
+/// magnifies part of the grid; it never shows anything outside it, so the zoom is at least 1 and
+/// the center is kept far enough from the edges to fill the screen.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Camera {
+    /// Magnification. 1.0 shows the whole grid, 2.0 shows a quarter of it, etc.
+    pub zoom: f32,
+    /// The point shown in the center of the screen, in cells (so `(0.0, 0.0)` is the top left
+    /// corner of the grid, and `(0.5, 0.5)` the center of the top left cell).
+    pub center: (f32, f32),
+}
+
+impl Camera {
+    /// The camera that shows the whole of a grid of size `dimensions`.
+    pub fn full_view(dimensions: (u32, u32)) -> Self {
+        Camera {
+            zoom: 1.0,
+            center: (dimensions.0 as f32 / 2.0, dimensions.1 as f32 / 2.0),
+        }
+    }
+
+    /// Restrict the camera so that it stays within a grid of size `dimensions`.
+    fn clamped(self, dimensions: (u32, u32)) -> Self {
+        let zoom = if self.zoom.is_finite() {
+            self.zoom.max(1.0)
+        } else {
+            1.0
+        };
+        let clamp = |c: f32, size: u32| {
+            let half_view = size as f32 / (2.0 * zoom);
+            c.clamp(half_view, size as f32 - half_view)
+        };
+        Camera {
+            zoom,
+            center: (
+                clamp(self.center.0, dimensions.0),
+                clamp(self.center.1, dimensions.1),
+            ),
+        }
+    }
+
+    /// Blend between two cameras; `t` is in `[0, 1]`.
+    fn lerp(self, other: Camera, t: f32) -> Self {
+        let mix = |a: f32, b: f32| a + (b - a) * t;
+        Camera {
+            zoom: mix(self.zoom, other.zoom),
+            center: (
+                mix(self.center.0, other.center.0),
+                mix(self.center.1, other.center.1),
+            ),
+        }
+    }
+
+    /// Scale and offset that transform screen texture coordinates into render target texture
+    /// coordinates, for a grid of size `dimensions`.
+    fn uv_transform(&self, dimensions: (u32, u32)) -> ([f32; 2], [f32; 2]) {
+        let scale = 1.0 / self.zoom;
+        (
+            [scale, scale],
+            [
+                self.center.0 / dimensions.0 as f32 - scale / 2.0,
+                self.center.1 / dimensions.1 as f32 - scale / 2.0,
+            ],
+        )
+    }
+}
+
+/// A camera move in progress, see `RenderInterface::set_camera()`.
+#[derive(Copy, Clone, Debug)]
+struct CameraTransition {
+    from: Camera,
+    to: Camera,
+    start: time::Duration,
+    length: time::Duration,
+}
+
+impl CameraTransition {
+    /// A transition that has already finished at `camera`.
+    fn fixed(camera: Camera) -> Self {
+        CameraTransition {
+            from: camera,
+            to: camera,
+            start: time::Duration::ZERO,
+            length: time::Duration::ZERO,
+        }
+    }
+
+    /// The camera at time `now`. Transitions ease in and out.
+    fn camera_at(&self, now: time::Duration) -> Camera {
+        if now >= self.start + self.length {
+            return self.to;
+        }
+        let t = ((now - self.start) / self.length).clamp(0.0, 1.0) as f32;
+        self.from.lerp(self.to, t * t * (3.0 - 2.0 * t))
+    }
+}
+
+/// Create the texture that sprite cells are drawn into, before it is scaled onto the screen.
+fn create_render_target_view(device: &wgpu::Device, size: wgpu::Extent3d) -> wgpu::TextureView {
+    device
+        .create_texture(&wgpu::TextureDescriptor {
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+            label: Some("render target texture"),
+        })
+        .create_view(&Default::default())
+}
+
+/// Upload a sprite texture to the GPU.
+fn upload_sprite_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    sprite_texture: &SpriteTexture,
+) -> wgpu::TextureView {
+    let sprite_texture_size = wgpu::Extent3d {
+        width: sprite_texture.width() as _,
+        height: sprite_texture.height() as _,
+        depth_or_array_layers: 1,
+    };
+
+    let sprite_texture_gpu = device.create_texture(&wgpu::TextureDescriptor {
+        size: sprite_texture_size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::R8Uint,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        label: Some("sprite texture"),
+    });
+
+    for y in 0..sprite_texture.height() {
+        trace!(
+            "{:?}",
+            &sprite_texture.pixels()
+                [y * sprite_texture.width()..y * sprite_texture.width() + sprite_texture.width()]
+                .iter()
+                .map(|i| format!("{}", i))
+                .collect::<String>()
+        );
+    }
+
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture: &sprite_texture_gpu,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        sprite_texture.pixels(),
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: std::num::NonZeroU32::new(sprite_texture.width() as u32),
+            rows_per_image: std::num::NonZeroU32::new(sprite_texture.height() as u32),
+        },
+        sprite_texture_size,
+    );
+
+    sprite_texture_gpu.create_view(&Default::default())
+}
+
+/// Run `f`, which creates shader modules and pipelines, and return any validation error it
+/// causes (e.g. a shader that doesn't compile) instead of sending it to wgpu's error handler,
+/// which panics. `label` identifies the shader in the error message.
+fn catch_validation_errors<T>(
+    device: &wgpu::Device,
+    label: &str,
+    f: impl FnOnce() -> T,
+) -> Result<T, RenderError> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let result = f();
+    match futures::executor::block_on(device.pop_error_scope()) {
+        Some(e) => Err(RenderError::ShaderError(format!("{}: {}", label, e))),
+        None => Ok(result),
+    }
+}
+
+/// Create a read-only storage buffer holding `contents`. Bindings can't be empty, so an empty slice
+/// gets a single zeroed element.
+fn create_storage_buffer<T: bytemuck::Pod>(
+    device: &wgpu::Device,
+    label: &str,
+    contents: &[T],
+) -> wgpu::Buffer {
+    let zeroed = [T::zeroed()];
+    let contents = if contents.is_empty() {
+        &zeroed
+    } else {
+        contents
+    };
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some(label),
+        contents: bytemuck::cast_slice(contents),
+        usage: wgpu::BufferUsages::STORAGE,
+    })
+}
+
+/// Create the bind group holding the cell uniforms and the registered animations and palette
+/// cycles.
+fn create_cell_uniform_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    uniform_buffer: &wgpu::Buffer,
+    tables: &AnimationTables,
+) -> wgpu::BindGroup {
+    let animation_buffer = create_storage_buffer(device, "Animation buffer", &tables.animations);
+    let animation_frame_buffer =
+        create_storage_buffer(device, "Animation frame buffer", &tables.animation_frames);
+    let palette_cycle_buffer =
+        create_storage_buffer(device, "Palette cycle buffer", &tables.palette_cycles);
+    let palette_cycle_color_buffer = create_storage_buffer(
+        device,
+        "Palette cycle color buffer",
+        &tables.palette_cycle_colors,
+    );
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Cell uniform bind group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: animation_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: animation_frame_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: palette_cycle_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: palette_cycle_color_buffer.as_entire_binding(),
+            },
+        ],
+    })
+}
+
+/// Compile a cell shader and create the cell pipeline. `source` must define `shade_cell()`; it is
+/// compiled between the shared declarations and the fragment shader entry point.
+fn create_cell_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    source: &str,
+    format: wgpu::TextureFormat,
+) -> Result<wgpu::RenderPipeline, RenderError> {
+    catch_validation_errors(device, "Cell shader", || {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Cell shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                format!(
+                    "{}\n{}\n{}",
+                    include_str!("render/shader/cell_common.wgsl"),
+                    source,
+                    include_str!("render/shader/cell_main.wgsl")
+                )
+                .into(),
+            ),
+        });
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Cell render pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: "vs_main",
+                buffers: &[Vertex::layout(), Instance::layout()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    })
+}
+
+fn create_screen_texture_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    render_target_view: &wgpu::TextureView,
+    render_target_sampler: &wgpu::Sampler,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(render_target_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(render_target_sampler),
+            },
+        ],
+        label: Some("screen_texture_bind_group"),
+    })
+}
+
 pub(crate) struct Renderer {
     pub(crate) device: wgpu::Device,
     pub(crate) queue: wgpu::Queue,
 
     render_output: RenderOutput,
 
+    cell_pipeline_layout: wgpu::PipelineLayout,
     cell_render_pipeline: wgpu::RenderPipeline,
+    // The default cell shader, drawing straight to the output. The statistics overlay is drawn
+    // with this after everything else, so the camera, post-processing and any custom cell shader
+    // don't apply to it.
+    overlay_render_pipeline: wgpu::RenderPipeline,
 
     cell_vertex_buffer: wgpu::Buffer,
     cell_index_buffer: wgpu::Buffer,
 
     cell_uniform_buffer: wgpu::Buffer,
+    cell_uniform_bind_group_layout: wgpu::BindGroupLayout,
     cell_uniform_bind_group: wgpu::BindGroup,
 
-    cell_texture_bind_group: wgpu::BindGroup,
+    animation_tables: AnimationTables,
 
-    render_target_view: wgpu::TextureView,
+    cell_texture_bind_group_layout: wgpu::BindGroupLayout,
+    sprite_texture_view: wgpu::TextureView,
 
-    instance_buffer: wgpu::Buffer,
+    render_target_view: wgpu::TextureView,
+    render_target_sampler: wgpu::Sampler,
 
+    screen_pipeline_layout: wgpu::PipelineLayout,
     screen_render_pipeline: wgpu::RenderPipeline,
 
     screen_vertex_buffer: wgpu::Buffer,
     screen_index_buffer: wgpu::Buffer,
+    screen_texture_bind_group_layout: wgpu::BindGroupLayout,
     screen_texture_bind_group: wgpu::BindGroup,
     screen_uniform_buffer: wgpu::Buffer,
     screen_uniform_bind_group: wgpu::BindGroup,
 
-    instances: Box<[Instance]>,
-    palette_data: Box<[[[u8; 3]; 16]]>,
-    palette_texture: wgpu::Texture,
+    post_process: post_process::PostProcessChain,
+
+    // Layers are drawn in order, so the last layer is on top.
+    layers: Vec<Layer>,
     palette_texture_size: wgpu::Extent3d,
+    cell_uniforms: CellGlobals,
 
     pub(crate) pixel_dimensions: (u32, u32),
-    pub(crate) aspect_ratio: (u32, u32),
     pub(crate) dimensions: (u32, u32),
+    pub(crate) scaling_mode: ScalingMode,
+
+    camera_transition: CameraTransition,
+    // The camera used for the most recently rendered frame, i.e. what is on screen.
+    camera: Camera,
 
     clear_color: wgpu::Color,
 
     last_render_time: time::OffsetDateTime,
     elapsed_time: time::Duration,
     frame_counter: u32,
-    fps: f32,
+    stats: FrameStats,
+    // Present while GPU timing is enabled.
+    gpu_timer: Option<stats::GpuTimer>,
+    // Drawn over the finished frame while the statistics overlay is shown.
+    stats_overlay: Option<Layer>,
 }
 
 impl Renderer {
@@ -262,24 +1143,8 @@ impl Renderer {
         clear_color: crate::resources::color::Color,
         screen_filter_method: wgpu::FilterMode,
         present_mode: wgpu::PresentMode,
+        force_fallback_adapter: bool,
     ) -> Result<Self, RenderError> {
-        let mut instances = vec![Instance::default(); (dimensions.0 * dimensions.1) as usize];
-        let palette_data = vec![[[255, 255, 255]; 16]; (dimensions.0 * dimensions.1) as usize];
-
-        for y in 0..dimensions.1 {
-            for x in 0..dimensions.0 {
-                instances[(y * dimensions.0 + x) as usize] = Instance {
-                    translate: [
-                        -1.0 + (x as f32 * 2.0 / dimensions.0 as f32),
-                        1.0 - ((y as f32 + 1.0) * 2.0 / dimensions.1 as f32),
-                    ],
-                    cell_coords: [x as _, y as _],
-                    sprite: 0,
-                    index: (y * dimensions.0 + x) as u32,
-                };
-            }
-        }
-
         let screen_width = dimensions.0 * sprite_texture.sprite_width() as u32;
         let screen_height = dimensions.1 * sprite_texture.sprite_height() as u32;
 
@@ -294,19 +1159,20 @@ impl Renderer {
             futures::executor::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
                 power_preference: wgpu::PowerPreference::default(),
                 compatible_surface: surface.as_ref(),
-                force_fallback_adapter: false,
+                force_fallback_adapter,
             }))
-            .expect("Failed to find an appropriate adapter");
+            .ok_or("Failed to find an appropriate adapter")?;
 
         let (device, queue) = futures::executor::block_on(adapter.request_device(
             &wgpu::DeviceDescriptor {
                 label: Some("Primary device"),
-                features: wgpu::Features::empty(),
+                // Timestamp queries are only used if GPU timing is turned on, but they have to be
+                // requested up front.
+                features: adapter.features() & wgpu::Features::TIMESTAMP_QUERY,
                 limits: wgpu::Limits::default(),
             },
             None,
-        ))
-        .expect("Failed to create device");
+        ))?;
 
         let render_target_size = wgpu::Extent3d {
             width: screen_width as _,
@@ -315,24 +1181,7 @@ impl Renderer {
         };
 
         let render_output = surface.map_or_else(
-            || {
-                let output_texture = device.create_texture(&wgpu::TextureDescriptor {
-                    size: render_target_size,
-                    mip_level_count: 1,
-                    sample_count: 1,
-                    dimension: wgpu::TextureDimension::D2,
-                    format: wgpu::TextureFormat::Rgba8Unorm,
-                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
-                    label: Some("final output texture"),
-                });
-
-                let texture_view = output_texture.create_view(&Default::default());
-                RenderOutput::Texture {
-                    texture: output_texture,
-                    texture_view,
-                    output_size: render_target_size,
-                }
-            },
+            || RenderOutput::new_texture(&device, render_target_size),
             |surface| {
                 let surface_configuration = wgpu::SurfaceConfiguration {
                     usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
@@ -357,92 +1206,21 @@ impl Renderer {
             },
         );
 
-        let render_target_texture = device.create_texture(&wgpu::TextureDescriptor {
-            size: render_target_size,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
-                | wgpu::TextureUsages::COPY_SRC
-                | wgpu::TextureUsages::TEXTURE_BINDING,
-            label: Some("render target texture"),
-        });
-
         let render_target_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
             address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: screen_filter_method,
-            min_filter: screen_filter_method,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
-
-        let render_target_view = render_target_texture.create_view(&Default::default());
-
-        let sprite_texture_size = wgpu::Extent3d {
-            width: sprite_texture.width() as _,
-            height: sprite_texture.height() as _,
-            depth_or_array_layers: 1,
-        };
-
-        let sprite_texture_gpu = device.create_texture(&wgpu::TextureDescriptor {
-            size: sprite_texture_size,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::R8Uint,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            label: Some("sprite texture"),
-        });
-
-        for y in 0..sprite_texture.height() {
-            trace!(
-                "{:?}",
-                &sprite_texture.pixels()[y * sprite_texture.width()
-                    ..y * sprite_texture.width() + sprite_texture.width()]
-                    .iter()
-                    .map(|i| format!("{}", i))
-                    .collect::<String>()
-            );
-        }
-
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &sprite_texture_gpu,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            sprite_texture.pixels(),
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: std::num::NonZeroU32::new(sprite_texture.width() as u32),
-                rows_per_image: std::num::NonZeroU32::new(sprite_texture.height() as u32),
-            },
-            sprite_texture_size,
-        );
-
-        let sprite_texture_view = sprite_texture_gpu.create_view(&Default::default());
-
-        let palette_texture_size = wgpu::Extent3d {
-            width: 16,
-            height: dimensions.0 as u32,
-            depth_or_array_layers: dimensions.1 as u32,
-        };
-
-        let palette_texture = device.create_texture(&wgpu::TextureDescriptor {
-            size: palette_texture_size,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D3,
-            format: wgpu::TextureFormat::Rgba8Unorm,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            label: Some("palette texture"),
+            mag_filter: screen_filter_method,
+            min_filter: screen_filter_method,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
         });
 
-        let palette_texture_view = palette_texture.create_view(&Default::default());
+        let render_target_view = create_render_target_view(&device, render_target_size);
+
+        let sprite_texture_view = upload_sprite_texture(&device, &queue, sprite_texture);
+
+        let palette_texture_size = palette_texture_size(dimensions);
 
         let cell_texture_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -471,34 +1249,14 @@ impl Renderer {
                 label: Some("cell_texture_bind_group_layout"),
             });
 
-        let cell_texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &cell_texture_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&sprite_texture_view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::TextureView(&palette_texture_view),
-                },
-            ],
-            label: Some("cell_texture_bind_group"),
-        });
-
-        let cell_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Cell shader"),
-            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!(
-                "render/shader/cell.wgsl"
-            ))),
-        });
-
-        let screen_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Screen shader"),
-            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!(
-                "render/shader/screen.wgsl"
-            ))),
-        });
+        let layers = vec![Layer::new(
+            &device,
+            dimensions,
+            &cell_texture_bind_group_layout,
+            &sprite_texture_view,
+            palette_texture_size,
+            true,
+        )];
 
         let cell_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Cell vertex buffer"),
@@ -515,34 +1273,66 @@ impl Renderer {
         let cell_uniform_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: Some("Cell uniform bind group layout"),
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
                     },
-                    count: None,
-                }],
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
             });
 
-        let cell_uniforms = CellGlobals {
+        let mut cell_uniforms = CellGlobals {
             screen_size_in_sprites: [dimensions.0 as _, dimensions.1 as _],
-            sprite_map_dimensions: [
-                (sprite_texture.width() / sprite_texture.sprite_width()) as u32,
-                (sprite_texture.height() / sprite_texture.sprite_height()) as u32,
-            ],
-            sprite_texture_dimensions: [
-                sprite_texture.width() as u32,
-                sprite_texture.height() as u32,
-            ],
-            sprite_dimensions: [
-                sprite_texture.sprite_width() as u32,
-                sprite_texture.sprite_height() as u32,
-            ],
             palette_texture_dimensions: [palette_texture_size.width, palette_texture_size.height],
+            ..Default::default()
         };
+        cell_uniforms.set_sprite_texture(sprite_texture);
 
         let cell_uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Cell uniform buffer"),
@@ -550,20 +1340,12 @@ impl Renderer {
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
-        let cell_uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Cell uniform bind group"),
-            layout: &cell_uniform_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: cell_uniform_buffer.as_entire_binding(),
-            }],
-        });
-
-        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Instance buffer"),
-            contents: bytemuck::cast_slice(&instances),
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-        });
+        let cell_uniform_bind_group = create_cell_uniform_bind_group(
+            &device,
+            &cell_uniform_bind_group_layout,
+            &cell_uniform_buffer,
+            &AnimationTables::default(),
+        );
 
         let cell_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Cell pipeline layout"),
@@ -574,24 +1356,12 @@ impl Renderer {
             push_constant_ranges: &[],
         });
 
-        let cell_render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Cell render pipeline"),
-            layout: Some(&cell_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &cell_shader,
-                entry_point: "vs_main",
-                buffers: &[Vertex::layout(), Instance::layout()],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &cell_shader,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::TextureFormat::Rgba8Unorm.into())],
-            }),
-            primitive: wgpu::PrimitiveState::default(),
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState::default(),
-            multiview: None,
-        });
+        let cell_render_pipeline = create_cell_pipeline(
+            &device,
+            &cell_pipeline_layout,
+            include_str!("render/shader/cell.wgsl"),
+            wgpu::TextureFormat::Rgba8Unorm,
+        )?;
 
         let screen_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Screen vertex buffer"),
@@ -628,20 +1398,12 @@ impl Renderer {
                 label: Some("screen_texture_bind_group_layout"),
             });
 
-        let screen_texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &screen_texture_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&render_target_view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&render_target_sampler),
-                },
-            ],
-            label: Some("screen_texture_bind_group"),
-        });
+        let screen_texture_bind_group = create_screen_texture_bind_group(
+            &device,
+            &screen_texture_bind_group_layout,
+            &render_target_view,
+            &render_target_sampler,
+        );
 
         let screen_uniform_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -684,42 +1446,29 @@ impl Renderer {
                 push_constant_ranges: &[],
             });
 
-        let screen_render_pipeline =
-            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("screen pipeline"),
-                layout: Some(&screen_pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &screen_shader,
-                    entry_point: "vs_main",
-                    buffers: &[Vertex::layout()],
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &screen_shader,
-                    entry_point: "fs_main",
-                    targets: &[Some(render_output.output_format().into())],
-                }),
-                primitive: wgpu::PrimitiveState::default(),
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState::default(),
-                multiview: None,
-            });
-
-        // Calculate aspect ratio. This is used for letterboxing the screen when the window's
-        // aspect ratio doesn't match.
-        let (mut ax, mut ay) = (screen_width, screen_height);
-        fn gcd(mut a: u32, mut b: u32) -> u32 {
-            while b != 0 {
-                let t = b;
-                b = a % b;
-                a = t;
-            }
-            a
-        }
-        let g = gcd(ax, ay);
-        ax /= g;
-        ay /= g;
+        let screen_render_pipeline = post_process::create_screen_pipeline(
+            &device,
+            &screen_pipeline_layout,
+            "Screen shader",
+            post_process::DEFAULT_SCREEN_SHADER,
+            "vs_main",
+            render_output.output_format(),
+        )?;
+
+        let post_process = post_process::PostProcessChain::new(
+            &device,
+            &screen_texture_bind_group_layout,
+            &screen_uniform_bind_group_layout,
+            render_output.output_format(),
+        )?;
+
+        let overlay_render_pipeline = create_cell_pipeline(
+            &device,
+            &cell_pipeline_layout,
+            include_str!("render/shader/cell.wgsl"),
+            render_output.output_format(),
+        )?;
 
-        info!("Aspect ratio: {}:{}", ax, ay);
         info!("surface format: {:?}", surface_format);
 
         Ok(Renderer {
@@ -728,91 +1477,107 @@ impl Renderer {
 
             render_output,
 
+            cell_pipeline_layout,
             cell_render_pipeline,
+            overlay_render_pipeline,
             cell_vertex_buffer,
             cell_index_buffer,
             cell_uniform_buffer,
+            cell_uniform_bind_group_layout,
             cell_uniform_bind_group,
-            cell_texture_bind_group,
+            animation_tables: AnimationTables::default(),
+            cell_texture_bind_group_layout,
+            sprite_texture_view,
 
+            screen_pipeline_layout,
             screen_render_pipeline,
             screen_vertex_buffer,
             screen_index_buffer,
+            screen_texture_bind_group_layout,
             screen_texture_bind_group,
             screen_uniform_buffer,
             screen_uniform_bind_group,
 
-            instances: instances.into_boxed_slice(),
-            instance_buffer,
+            post_process,
 
-            palette_data: palette_data.into_boxed_slice(),
-            palette_texture,
+            layers,
             palette_texture_size,
+            cell_uniforms,
 
             render_target_view,
-            aspect_ratio: (ax, ay),
+            render_target_sampler,
             pixel_dimensions: (screen_width as _, screen_height as _),
             dimensions,
+            scaling_mode: ScalingMode::Fit,
+
+            camera_transition: CameraTransition::fixed(Camera::full_view(dimensions)),
+            camera: Camera::full_view(dimensions),
 
             clear_color: clear_color.into(),
             last_render_time: time::OffsetDateTime::now_utc(),
             elapsed_time: time::Duration::ZERO,
             frame_counter: 0,
-            fps: 0.0,
+            stats: FrameStats::default(),
+            gpu_timer: None,
+            stats_overlay: None,
         })
     }
 
     pub(crate) fn render_frame(&mut self) -> Result<(), RenderError> {
+        let render_start = std::time::Instant::now();
         let (screen_w, screen_h) = self.render_output.output_size();
-        let (ax, ay) = self.aspect_ratio;
-        let target_w = std::cmp::min(screen_w, (screen_h * ax) / ay);
-        let target_h = std::cmp::min(screen_h, (screen_w * ay) / ax);
+        let viewport = self.viewport();
+        self.camera = self.camera_transition.camera_at(self.elapsed_time);
+        let (uv_scale, uv_offset) = self.camera.uv_transform(self.dimensions);
 
         let screen_uniforms = ScreenGlobals {
             screen_size: [screen_w as _, screen_h as _],
             frame_counter: self.frame_counter,
             elapsed_time: self.elapsed_time.as_seconds_f32(),
             scale_factor: [
-                target_w as f32 / screen_w as f32,
-                target_h as f32 / screen_h as f32,
+                viewport.width as f32 / screen_w as f32,
+                viewport.height as f32 / screen_h as f32,
+            ],
+            // Offset of the viewport's center from the screen's center, in clip space. This is
+            // nonzero when the margins on either side differ by a pixel, which keeps the image
+            // aligned to the pixel grid.
+            translate: [
+                (2 * viewport.x + viewport.width as i32 - screen_w as i32) as f32 / screen_w as f32,
+                -(2 * viewport.y + viewport.height as i32 - screen_h as i32) as f32
+                    / screen_h as f32,
             ],
+            uv_scale,
+            uv_offset,
+            viewport: [
+                viewport.x as f32,
+                viewport.y as f32,
+                viewport.width as f32,
+                viewport.height as f32,
+            ],
+            source_size: [self.pixel_dimensions.0 as _, self.pixel_dimensions.1 as _],
+            _padding: [0.0; 2],
         };
 
-        // TODO: update palette data in-place in update() instead of making a copy here.
-        let flat_palette_data: Vec<u8> = self
-            .palette_data
-            .iter()
-            .flat_map(|c| c.iter())
-            .flat_map(|c| vec![c[0], c[1], c[2], 255].into_iter())
-            .collect();
-
         self.queue.write_buffer(
             &self.screen_uniform_buffer,
             0,
             bytemuck::cast_slice(&[screen_uniforms]),
         );
 
+        self.cell_uniforms.elapsed_time = screen_uniforms.elapsed_time;
+        self.cell_uniforms.frame_counter = self.frame_counter;
         self.queue.write_buffer(
-            &self.instance_buffer,
+            &self.cell_uniform_buffer,
             0,
-            bytemuck::cast_slice(&self.instances[..]),
+            bytemuck::cast_slice(&[self.cell_uniforms]),
         );
 
-        self.queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &self.palette_texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            &flat_palette_data[..],
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: std::num::NonZeroU32::new(16 * 4),
-                rows_per_image: std::num::NonZeroU32::new(self.dimensions.0 as u32),
-            },
-            self.palette_texture_size,
-        );
+        if self.frame_counter % OVERLAY_REFRESH_FRAMES == 0 {
+            self.refresh_stats_overlay();
+        }
+        for layer in self.layers.iter_mut().chain(self.stats_overlay.iter_mut()) {
+            layer.upload(&self.queue, self.palette_texture_size);
+        }
 
         // If we are rendering to a surface, the SurfaceTexture must live until we finish rendering
         // the frame.
@@ -836,6 +1601,9 @@ impl Renderer {
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
                 label: Some("Main render encoder"),
             });
+        if let Some(timer) = &self.gpu_timer {
+            timer.begin(&mut encoder);
+        }
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Main sprite cell pass"),
@@ -856,19 +1624,38 @@ impl Renderer {
             });
             render_pass.set_pipeline(&self.cell_render_pipeline);
             render_pass.set_bind_group(0, &self.cell_uniform_bind_group, &[]);
-            render_pass.set_bind_group(1, &self.cell_texture_bind_group, &[]);
             render_pass.set_vertex_buffer(0, self.cell_vertex_buffer.slice(..));
-            render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
             render_pass
                 .set_index_buffer(self.cell_index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-            render_pass.draw_indexed(0..QUAD_INDICES.len() as _, 0, 0..self.instances.len() as _);
+            for layer in self.layers.iter() {
+                render_pass.set_bind_group(1, &layer.texture_bind_group, &[]);
+                render_pass.set_vertex_buffer(1, layer.instance_buffer.slice(..));
+                render_pass.draw_indexed(
+                    0..QUAD_INDICES.len() as _,
+                    0,
+                    0..layer.instances.len() as _,
+                );
+            }
         }
 
+        // With post-processing, the screen pass renders into the first intermediate texture of
+        // the chain rather than the output.
+        let post_processing = self.post_process.is_active();
+        let screen_pass_view = if post_processing {
+            self.post_process.input_view(
+                &self.device,
+                (screen_w, screen_h),
+                &self.screen_texture_bind_group_layout,
+                &self.render_target_sampler,
+            )
+        } else {
+            output_texture_view
+        };
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Screen pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: output_texture_view,
+                    view: screen_pass_view,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(self.clear_color),
@@ -885,22 +1672,75 @@ impl Renderer {
                 .set_index_buffer(self.cell_index_buffer.slice(..), wgpu::IndexFormat::Uint16);
             render_pass.draw_indexed(0..QUAD_INDICES.len() as _, 0, 0..1);
         }
+        if post_processing {
+            self.post_process.encode(
+                &mut encoder,
+                &self.screen_uniform_bind_group,
+                &self.screen_vertex_buffer,
+                &self.cell_index_buffer,
+                output_texture_view,
+            );
+        }
+        // The overlay covers the visible part of the viewport, at the grid's unmagnified scale.
+        if let (Some(overlay), Some(visible)) = (
+            &self.stats_overlay,
+            viewport.visible_part((screen_w, screen_h)),
+        ) {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Statistics overlay pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: output_texture_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_viewport(
+                visible.x as f32,
+                visible.y as f32,
+                visible.width as f32,
+                visible.height as f32,
+                0.0,
+                1.0,
+            );
+            render_pass.set_pipeline(&self.overlay_render_pipeline);
+            render_pass.set_bind_group(0, &self.cell_uniform_bind_group, &[]);
+            render_pass.set_bind_group(1, &overlay.texture_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, self.cell_vertex_buffer.slice(..));
+            render_pass.set_vertex_buffer(1, overlay.instance_buffer.slice(..));
+            render_pass
+                .set_index_buffer(self.cell_index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            render_pass.draw_indexed(
+                0..QUAD_INDICES.len() as _,
+                0,
+                0..overlay.instances.len() as _,
+            );
+        }
+        if let Some(timer) = &self.gpu_timer {
+            timer.end(&mut encoder);
+        }
         self.queue.submit(Some(encoder.finish()));
 
         if let Some(surface_texture) = surface_texture.take() {
             surface_texture.present();
         }
+        self.stats.render_time.record(render_start.elapsed());
+        if let Some(gpu_time) = self.gpu_timer.as_ref().and_then(|t| t.read(&self.device)) {
+            self.stats.gpu_time.record(gpu_time);
+        }
 
         let t = time::OffsetDateTime::now_utc();
         let dt = t - self.last_render_time;
-        let dt_micros = dt.whole_microseconds();
-        if dt_micros > 0 {
-            let new_fps = 1_000_000.0 / dt_micros as f32;
-            self.fps = 0.9 * self.fps + 0.1 * new_fps;
+        if dt.is_positive() {
             self.elapsed_time += dt;
         }
+        self.stats
+            .record_frame(dt.try_into().unwrap_or(std::time::Duration::ZERO));
         if self.frame_counter % 1000 == 0 {
-            info!("{} FPS", self.fps);
+            info!("{} FPS", self.stats.fps());
         }
         self.last_render_time = t;
         self.frame_counter += 1;
@@ -923,6 +1763,173 @@ impl Renderer {
         }
     }
 
+    /// Replace the fragment shader of the screen pass, which draws the render target into the
+    /// viewport. See `PostProcessPass` for what the shader has access to; the screen pass has no
+    /// uniforms of its own. On error, the current shader is kept.
+    pub(crate) fn set_screen_shader(&mut self, source: &str) -> Result<(), RenderError> {
+        self.screen_render_pipeline = post_process::create_screen_pipeline(
+            &self.device,
+            &self.screen_pipeline_layout,
+            "Screen shader",
+            source,
+            "vs_main",
+            self.render_output.output_format(),
+        )?;
+        Ok(())
+    }
+
+    /// Replace the cell shader, which colors each pixel of each sprite cell. On error, the current
+    /// shader is kept. See `WindowBuilder::with_cell_shader()`.
+    pub(crate) fn set_cell_shader(&mut self, source: &str) -> Result<(), RenderError> {
+        self.cell_render_pipeline = create_cell_pipeline(
+            &self.device,
+            &self.cell_pipeline_layout,
+            source,
+            wgpu::TextureFormat::Rgba8Unorm,
+        )?;
+        Ok(())
+    }
+
+    /// Record how long the driver spent in `Driver::process_frame()` for the upcoming frame.
+    pub(crate) fn record_process_time(&mut self, process_time: std::time::Duration) {
+        self.stats.process_time.record(process_time);
+    }
+
+    /// Redraw the statistics overlay, if it is shown, with the current statistics.
+    fn refresh_stats_overlay(&mut self) {
+        if let Some(overlay) = &mut self.stats_overlay {
+            let cells = stats::overlay_cells(
+                &self.stats.overlay_lines(),
+                self.dimensions.0 as usize,
+                self.dimensions.1 as usize,
+            );
+            overlay.update(cells.iter(), self.elapsed_time.as_seconds_f32());
+        }
+    }
+
+    /// Add a pass to the end of the post-processing chain.
+    pub(crate) fn add_post_process(&mut self, pass: &PostProcessPass) -> Result<(), RenderError> {
+        self.post_process.push(&self.device, pass)
+    }
+
+    /// Size of a single sprite, in pixels.
+    pub(crate) fn sprite_dimensions(&self) -> (u32, u32) {
+        let [w, h] = self.cell_uniforms.sprite_dimensions;
+        (w, h)
+    }
+
+    /// Change the size of the sprite grid. The render target, and offscreen output if any, are
+    /// resized to match, and every layer is reset to its initial contents.
+    pub(crate) fn resize_grid(&mut self, dimensions: (u32, u32)) {
+        let dimensions = (dimensions.0.max(1), dimensions.1.max(1));
+        if dimensions == self.dimensions {
+            return;
+        }
+        self.palette_texture_size = palette_texture_size(dimensions);
+        self.cell_uniforms.screen_size_in_sprites = [dimensions.0, dimensions.1];
+        self.cell_uniforms.palette_texture_dimensions = [
+            self.palette_texture_size.width,
+            self.palette_texture_size.height,
+        ];
+        self.queue.write_buffer(
+            &self.cell_uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.cell_uniforms]),
+        );
+
+        self.dimensions = dimensions;
+        let layer_count = self.layers.len();
+        self.layers = (0..layer_count)
+            .map(|i| {
+                Layer::new(
+                    &self.device,
+                    dimensions,
+                    &self.cell_texture_bind_group_layout,
+                    &self.sprite_texture_view,
+                    self.palette_texture_size,
+                    i == 0,
+                )
+            })
+            .collect();
+        if self.stats_overlay.is_some() {
+            self.stats_overlay = Some(Layer::new(
+                &self.device,
+                dimensions,
+                &self.cell_texture_bind_group_layout,
+                &self.sprite_texture_view,
+                self.palette_texture_size,
+                false,
+            ));
+            self.refresh_stats_overlay();
+        }
+
+        self.resize_render_target();
+        self.camera = Camera::full_view(dimensions);
+        self.camera_transition = CameraTransition::fixed(self.camera);
+        info!("Grid resized to {}x{}", dimensions.0, dimensions.1);
+    }
+
+    /// Upload the animation tables again after an animation or palette cycle is added.
+    fn update_animation_tables(&mut self) {
+        self.cell_uniform_bind_group = create_cell_uniform_bind_group(
+            &self.device,
+            &self.cell_uniform_bind_group_layout,
+            &self.cell_uniform_buffer,
+            &self.animation_tables,
+        );
+    }
+
+    /// Recreate the render target (and offscreen output, if any) to fit the grid, after the grid
+    /// or sprite size changes.
+    fn resize_render_target(&mut self) {
+        let [sprite_width, sprite_height] = self.cell_uniforms.sprite_dimensions;
+        let (screen_width, screen_height) = (
+            self.dimensions.0 * sprite_width,
+            self.dimensions.1 * sprite_height,
+        );
+        let render_target_size = wgpu::Extent3d {
+            width: screen_width,
+            height: screen_height,
+            depth_or_array_layers: 1,
+        };
+
+        self.render_target_view = create_render_target_view(&self.device, render_target_size);
+        self.screen_texture_bind_group = create_screen_texture_bind_group(
+            &self.device,
+            &self.screen_texture_bind_group_layout,
+            &self.render_target_view,
+            &self.render_target_sampler,
+        );
+        if let RenderOutput::Texture { .. } = self.render_output {
+            self.render_output = RenderOutput::new_texture(&self.device, render_target_size);
+        }
+        self.pixel_dimensions = (screen_width, screen_height);
+    }
+
+    /// The area of the output that the grid is currently scaled into.
+    pub(crate) fn viewport(&self) -> Viewport {
+        Viewport::new(
+            self.render_output.output_size(),
+            self.pixel_dimensions,
+            self.scaling_mode,
+        )
+    }
+
+    /// Map a position in the output (e.g. the cursor position in the window) to the cell under
+    /// it, or `None` if the position is outside the grid. This accounts for the camera, as of the
+    /// last rendered frame.
+    pub(crate) fn screen_to_cell(&self, position: (f64, f64)) -> Option<(u32, u32)> {
+        let (u, v) = self.viewport().uv_at(position)?;
+        let ([scale_u, scale_v], [offset_u, offset_v]) = self.camera.uv_transform(self.dimensions);
+        Some(uv_to_cell(
+            (
+                u * scale_u as f64 + offset_u as f64,
+                v * scale_v as f64 + offset_v as f64,
+            ),
+            self.dimensions,
+        ))
+    }
+
     pub(crate) fn fetch_render_output(&self) -> Option<Box<[u8]>> {
         if let RenderOutput::Texture {
             texture,
@@ -1000,32 +2007,260 @@ impl RenderInterface for Renderer {
         T: Iterator<Item = U>,
         U: Into<&'a SpriteCell>,
     {
-        for (i, d, p) in itertools::multizip((
-            self.instances.iter_mut(),
-            data,
-            self.palette_data.iter_mut(),
-        )) {
-            let c: &SpriteCell = d.into();
-            i.sprite = c.sprite;
-            *p = c.palette.into();
+        self.update_layer(0, data);
+    }
+
+    fn update_layer<'a, T, U>(&mut self, layer: usize, data: T)
+    where
+        T: Iterator<Item = U>,
+        U: Into<&'a SpriteCell>,
+    {
+        let now = self.elapsed_time.as_seconds_f32();
+        self.layers[layer].update(data, now);
+    }
+
+    fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    fn set_layer_count(&mut self, count: usize) {
+        let count = count.max(1);
+        self.layers.truncate(count);
+        while self.layers.len() < count {
+            self.layers.push(Layer::new(
+                &self.device,
+                self.dimensions,
+                &self.cell_texture_bind_group_layout,
+                &self.sprite_texture_view,
+                self.palette_texture_size,
+                false,
+            ));
         }
     }
 
     /// Get the current frames per second. This is based on a rolling average, not an
     /// instantaneous measurement.
     fn get_fps(&self) -> f32 {
-        self.fps
+        self.stats.fps()
+    }
+
+    fn dimensions(&self) -> (u32, u32) {
+        self.dimensions
+    }
+
+    fn set_camera(&mut self, camera: Camera, transition: std::time::Duration) {
+        let now = self.elapsed_time;
+        self.camera_transition = CameraTransition {
+            from: self.camera_transition.camera_at(now),
+            to: camera.clamped(self.dimensions),
+            start: now,
+            length: transition.try_into().unwrap_or(time::Duration::ZERO),
+        };
+    }
+
+    fn camera(&self) -> Camera {
+        self.camera_transition.to
+    }
+
+    fn set_post_process_uniforms<T: bytemuck::Pod>(&mut self, pass: usize, uniforms: T) {
+        self.post_process.set_uniforms(
+            &self.device,
+            &self.queue,
+            pass,
+            bytemuck::bytes_of(&uniforms),
+        );
+    }
+
+    fn set_post_process_enabled(&mut self, pass: usize, enabled: bool) {
+        self.post_process.set_enabled(pass, enabled);
+    }
+
+    fn set_sprite_texture(&mut self, sprite_texture: &SpriteTexture) {
+        let old_sprite_dimensions = self.cell_uniforms.sprite_dimensions;
+        self.sprite_texture_view = upload_sprite_texture(&self.device, &self.queue, sprite_texture);
+        for layer in self.layers.iter_mut().chain(self.stats_overlay.iter_mut()) {
+            layer.set_sprite_texture(
+                &self.device,
+                &self.cell_texture_bind_group_layout,
+                &self.sprite_texture_view,
+            );
+        }
+        self.cell_uniforms.set_sprite_texture(sprite_texture);
+        if self.cell_uniforms.sprite_dimensions != old_sprite_dimensions {
+            self.resize_render_target();
+        }
+        info!(
+            "Sprite texture replaced; sprites are now {}x{}",
+            sprite_texture.sprite_width(),
+            sprite_texture.sprite_height()
+        );
+    }
+
+    fn add_animation(&mut self, animation: &Animation) -> AnimationId {
+        let id = self.animation_tables.add_animation(animation);
+        self.update_animation_tables();
+
+        id
+    }
+
+    fn add_palette_cycle(&mut self, cycle: &PaletteCycle) -> PaletteCycleId {
+        let id = self.animation_tables.add_palette_cycle(cycle);
+        self.update_animation_tables();
+
+        id
+    }
+
+    fn frame_stats(&self) -> Option<&FrameStats> {
+        Some(&self.stats)
+    }
+
+    fn set_stats_overlay(&mut self, visible: bool) {
+        if visible == self.stats_overlay.is_some() {
+            return;
+        }
+        self.stats_overlay = visible.then(|| {
+            Layer::new(
+                &self.device,
+                self.dimensions,
+                &self.cell_texture_bind_group_layout,
+                &self.sprite_texture_view,
+                self.palette_texture_size,
+                false,
+            )
+        });
+        self.refresh_stats_overlay();
+    }
+
+    fn set_gpu_timing(&mut self, enabled: bool) {
+        self.gpu_timer = if enabled {
+            let timer = stats::GpuTimer::new(&self.device, &self.queue);
+            if timer.is_none() {
+                info!("GPU timing isn't supported by this adapter");
+            }
+            timer
+        } else {
+            None
+        };
     }
 }
 
 /// Interface for EngineDriver -> Renderer communication.
+///
+/// The sprite matrix is made up of one or more layers, which are composited in order (layer 0 at
+/// the bottom). Cells marked as `transparent`, and palette indices keyed out with
+/// `SpriteCell::transparent_mask`, are alpha-blended so the layers below them show through.
 pub trait RenderInterface {
-    /// Update the sprite matrix with the provided data.
+    /// Update the sprite matrix with the provided data. This is equivalent to
+    /// `update_layer(0, data)`.
     fn update<'a, T, U>(&mut self, data: T)
     where
         T: Iterator<Item = U>,
         U: Into<&'a SpriteCell>;
 
+    /// Update a single layer of the sprite matrix with the provided data. Panics if `layer` is
+    /// not less than `layer_count()`.
+    fn update_layer<'a, T, U>(&mut self, layer: usize, data: T)
+    where
+        T: Iterator<Item = U>,
+        U: Into<&'a SpriteCell>;
+
+    /// Get the number of layers.
+    fn layer_count(&self) -> usize;
+
+    /// Set the number of layers. There is always at least one layer. New layers start out fully
+    /// transparent; removed layers are discarded from the top of the stack.
+    fn set_layer_count(&mut self, count: usize);
+
     /// Get the current FPS
     fn get_fps(&self) -> f32;
+
+    /// Get the size of the sprite grid, in cells.
+    fn dimensions(&self) -> (u32, u32);
+
+    /// Move the camera, zooming and panning the grid on screen. The camera moves smoothly from its
+    /// current position over `transition`; pass `Duration::ZERO` to move it immediately. Input
+    /// events report the cell under the cursor with the camera taken into account.
+    ///
+    /// Renderers that don't support a camera (the software and terminal renderers) ignore this.
+    fn set_camera(&mut self, _camera: Camera, _transition: std::time::Duration) {}
+
+    /// Get the camera's destination, as set by `set_camera()` (after clamping it to the grid).
+    fn camera(&self) -> Camera {
+        Camera::full_view(self.dimensions())
+    }
+
+    /// Replace the uniforms of post-processing pass `pass` (counting from 0 in the order the
+    /// passes were added). Does nothing if there is no such pass.
+    ///
+    /// Renderers without post-processing (the software and terminal renderers) ignore this.
+    fn set_post_process_uniforms<T: bytemuck::Pod>(&mut self, _pass: usize, _uniforms: T) {}
+
+    /// Turn post-processing pass `pass` on or off. Does nothing if there is no such pass.
+    ///
+    /// Renderers without post-processing (the software and terminal renderers) ignore this.
+    fn set_post_process_enabled(&mut self, _pass: usize, _enabled: bool) {}
+
+    /// Replace the sprite texture, e.g. to switch fonts. Layers keep their contents, so sprite
+    /// indices should mean the same thing in the new texture. If the new sprites are a different
+    /// size, the rendered image changes size with them: the window is resized to fit the grid or,
+    /// with `WindowBuilder::with_resizable_grid()`, the grid is resized to fit the window.
+    ///
+    /// Renderers that don't draw sprites (the terminal renderer) ignore this.
+    fn set_sprite_texture(&mut self, _sprite_texture: &SpriteTexture) {}
+
+    /// Register an animation, returning the ID to put in `SpriteCell::animation` to play it.
+    /// Animated cells pick their frame on the GPU, so they don't need to be updated while they
+    /// play.
+    ///
+    /// Renderers without animation support (the terminal renderer) show each cell's
+    /// `SpriteCell::sprite` instead.
+    fn add_animation(&mut self, _animation: &Animation) -> AnimationId {
+        AnimationId(0)
+    }
+
+    /// Register a palette cycle, returning the ID to put in `SpriteCell::palette_cycle` to apply
+    /// it. Cycled palettes are evaluated by the renderer every frame (on the GPU, for the wgpu
+    /// renderers), so cells using them don't need to be updated while the colors change.
+    ///
+    /// Renderers without palette cycling (the terminal renderer) show each cell's
+    /// `SpriteCell::palette` unchanged instead.
+    fn add_palette_cycle(&mut self, _cycle: &PaletteCycle) -> PaletteCycleId {
+        PaletteCycleId(0)
+    }
+
+    /// Get timing statistics for recent frames, or `None` for renderers that don't collect them
+    /// (the software and terminal renderers).
+    fn frame_stats(&self) -> Option<&FrameStats> {
+        None
+    }
+
+    /// Show or hide an overlay with the main frame statistics, drawn in the top left of the visible
+    /// grid with the sprite texture's font (sprite indices are character codes). The overlay is
+    /// drawn over the finished frame, so it stays in place when the camera moves and isn't
+    /// affected by post-processing. It isn't counted by `layer_count()`.
+    ///
+    /// Renderers that don't collect statistics ignore this.
+    fn set_stats_overlay(&mut self, _visible: bool) {}
+
+    /// Turn on measuring how long the GPU spends on each frame, recorded in
+    /// `FrameStats::gpu_time`. This makes the renderer wait for the GPU to finish every frame, so
+    /// it lowers throughput; leave it off unless you're profiling. Has no effect if the adapter
+    /// doesn't support timestamp queries.
+    ///
+    /// Renderers that don't collect statistics ignore this.
+    fn set_gpu_timing(&mut self, _enabled: bool) {}
+}
+
+/// A renderer that draws into memory instead of a window, so that its output can be read back.
+pub trait OffscreenRenderer: RenderInterface {
+    /// Get the size of the rendered image, in pixels.
+    fn output_size(&self) -> (u32, u32);
+
+    /// Render one frame and return it as RGBA8 pixels, in row-major order starting at the top
+    /// left.
+    fn capture_frame(&mut self) -> Result<Box<[u8]>, RenderError>;
+
+    /// Change the size of the sprite grid, in cells. All layers are reset to their initial
+    /// contents.
+    fn resize_grid(&mut self, width: u32, height: u32);
 }
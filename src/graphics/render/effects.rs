@@ -0,0 +1,134 @@
+// Copyright 2018 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::PostProcessPass;
+
+/// Color adjustments applied to the final image, independently of the palettes used to draw it.
+/// The default leaves colors unchanged.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ColorGrading {
+    /// Multiplier applied to all channels.
+    pub brightness: f32,
+    /// Contrast around mid-gray; 1.0 is unchanged, 0.0 is flat gray.
+    pub contrast: f32,
+    /// 1.0 is unchanged, 0.0 is grayscale, and values above 1.0 oversaturate.
+    pub saturation: f32,
+    /// Gamma correction exponent; values above 1.0 brighten the midtones.
+    pub gamma: f32,
+    /// Per-channel (red, green, blue) multiplier, e.g. `[0.2, 1.0, 0.2]` for a green monitor.
+    pub tint: [f32; 3],
+}
+
+impl Default for ColorGrading {
+    fn default() -> Self {
+        ColorGrading {
+            brightness: 1.0,
+            contrast: 1.0,
+            saturation: 1.0,
+            gamma: 1.0,
+            tint: [1.0, 1.0, 1.0],
+        }
+    }
+}
+
+/// Ready-made post-processing effects, mostly imitating a CRT monitor. Add them to a window with
+/// `WindowBuilder::with_effect()`, or turn one into a `PostProcessPass` with `into()`.
+///
+/// An effect's parameters can be changed while running by passing `Effect::uniforms()` for an
+/// effect of the same kind to `RenderInterface::set_post_process_uniforms()`, and it can be
+/// switched on and off with `RenderInterface::set_post_process_enabled()`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Effect {
+    /// Darkens the gap between rows of grid pixels. `intensity` ranges from 0.0 (no effect) to
+    /// 1.0 (black gaps).
+    Scanlines {
+        /// How dark the gaps are.
+        intensity: f32,
+    },
+    /// Bends the image as though on a curved screen. Useful values of `amount` are between 0.0
+    /// (flat) and about 0.2.
+    Curvature {
+        /// Strength of the distortion.
+        amount: f32,
+    },
+    /// Separates the red and blue channels towards the edges of the screen, like a poorly
+    /// converged CRT.
+    ChromaticAberration {
+        /// Separation at the edges of the screen, in window pixels.
+        offset: f32,
+    },
+    /// Darkens the corners of the screen. `intensity` ranges from 0.0 (no effect) to 1.0 (black
+    /// corners).
+    Vignette {
+        /// How dark the corners are.
+        intensity: f32,
+    },
+    /// Makes bright pixels fade out over several frames instead of disappearing at once, like
+    /// slow phosphor. Uses the previous frame, see `PostProcessPass::with_history()`.
+    PhosphorPersistence {
+        /// Fraction of brightness kept from one frame to the next, between 0.0 and 1.0.
+        decay: f32,
+    },
+    /// Adjusts the colors of the final image.
+    ColorGrading(ColorGrading),
+}
+
+impl Effect {
+    /// The effect's parameters, in the layout its shader expects. Pass these to
+    /// `RenderInterface::set_post_process_uniforms()` to change the parameters at runtime.
+    pub fn uniforms(&self) -> [f32; 8] {
+        match *self {
+            Effect::Scanlines { intensity } => [intensity, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+            Effect::Curvature { amount } => [amount, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+            Effect::ChromaticAberration { offset } => [offset, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+            Effect::Vignette { intensity } => [intensity, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+            Effect::PhosphorPersistence { decay } => [decay, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+            Effect::ColorGrading(g) => [
+                g.brightness,
+                g.contrast,
+                g.saturation,
+                g.gamma,
+                g.tint[0],
+                g.tint[1],
+                g.tint[2],
+                0.0,
+            ],
+        }
+    }
+
+    fn source(&self) -> &'static str {
+        match self {
+            Effect::Scanlines { .. } => include_str!("shader/effects/scanlines.wgsl"),
+            Effect::Curvature { .. } => include_str!("shader/effects/curvature.wgsl"),
+            Effect::ChromaticAberration { .. } => {
+                include_str!("shader/effects/chromatic_aberration.wgsl")
+            }
+            Effect::Vignette { .. } => include_str!("shader/effects/vignette.wgsl"),
+            Effect::PhosphorPersistence { .. } => {
+                include_str!("shader/effects/phosphor_persistence.wgsl")
+            }
+            Effect::ColorGrading(_) => include_str!("shader/effects/color_grading.wgsl"),
+        }
+    }
+}
+
+impl From<Effect> for PostProcessPass {
+    fn from(effect: Effect) -> Self {
+        let pass = PostProcessPass::new(effect.source()).with_uniforms(effect.uniforms());
+        match effect {
+            Effect::PhosphorPersistence { .. } => pass.with_history(),
+            _ => pass,
+        }
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright 2018 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{
+    Camera, FrameStats, OffscreenRenderer, PostProcessPass, RenderError, RenderInterface, Renderer,
+};
+use crate::graphics::animation::{Animation, AnimationId, PaletteCycle, PaletteCycleId};
+use crate::graphics::drawing::SpriteCell;
+use crate::resources::sprite::SpriteTexture;
+
+/// Selects which wgpu adapter a `HeadlessRenderer` runs on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AdapterSelection {
+    /// Whatever adapter wgpu considers the best match (usually a hardware GPU).
+    Default,
+    /// wgpu's software/fallback adapter, if the platform provides one. This is useful on CI
+    /// machines that don't have a GPU.
+    Fallback,
+}
+
+/// A renderer that draws into an offscreen texture instead of a window. Each call to
+/// `render_frame()` returns the rendered image as tightly packed RGBA8 pixels, which makes this
+/// suitable for golden tests and tooling.
+///
+/// The output image is exactly `width * sprite_width` by `height * sprite_height` pixels, so no
+/// scaling or letterboxing is applied.
+pub struct HeadlessRenderer {
+    renderer: Renderer,
+}
+
+impl HeadlessRenderer {
+    /// Create a new `HeadlessRenderer` with the given width and height (measured in sprites, not
+    /// pixels).
+    pub fn new(
+        width: u32,
+        height: u32,
+        sprite_texture: &SpriteTexture,
+        adapter: AdapterSelection,
+    ) -> Result<Self, RenderError> {
+        Ok(HeadlessRenderer {
+            renderer: Renderer::new(
+                None,
+                (width, height),
+                sprite_texture,
+                [0, 0, 0].into(),
+                wgpu::FilterMode::Nearest,
+                wgpu::PresentMode::Fifo,
+                adapter == AdapterSelection::Fallback,
+            )?,
+        })
+    }
+
+    /// Render one frame and return it as RGBA8 pixels, in row-major order starting at the top
+    /// left.
+    pub fn render_frame(&mut self) -> Result<Box<[u8]>, RenderError> {
+        self.renderer.render_frame()?;
+        self.renderer
+            .fetch_render_output()
+            .ok_or_else(|| RenderError::GeneralError("No offscreen render output".to_string()))
+    }
+
+    /// Get the size of the rendered image, in pixels.
+    pub fn output_size(&self) -> (u32, u32) {
+        self.renderer.pixel_dimensions
+    }
+
+    /// Change the size of the sprite grid (and with it, the rendered image). All layers are reset
+    /// to their initial contents.
+    pub fn resize_grid(&mut self, width: u32, height: u32) {
+        self.renderer.resize_grid((width, height));
+    }
+
+    /// Replace the screen shader. See `WindowBuilder::with_screen_shader()`.
+    pub fn set_screen_shader(&mut self, source: &str) -> Result<(), RenderError> {
+        self.renderer.set_screen_shader(source)
+    }
+
+    /// Replace the cell shader. See `WindowBuilder::with_cell_shader()`.
+    pub fn set_cell_shader(&mut self, source: &str) -> Result<(), RenderError> {
+        self.renderer.set_cell_shader(source)
+    }
+
+    /// Add a post-processing pass. See `WindowBuilder::with_post_process()`.
+    pub fn add_post_process(&mut self, pass: &PostProcessPass) -> Result<(), RenderError> {
+        self.renderer.add_post_process(pass)
+    }
+}
+
+impl OffscreenRenderer for HeadlessRenderer {
+    fn output_size(&self) -> (u32, u32) {
+        HeadlessRenderer::output_size(self)
+    }
+
+    fn capture_frame(&mut self) -> Result<Box<[u8]>, RenderError> {
+        self.render_frame()
+    }
+
+    fn resize_grid(&mut self, width: u32, height: u32) {
+        HeadlessRenderer::resize_grid(self, width, height)
+    }
+}
+
+impl RenderInterface for HeadlessRenderer {
+    fn update<'a, T, U>(&mut self, data: T)
+    where
+        T: Iterator<Item = U>,
+        U: Into<&'a SpriteCell>,
+    {
+        self.renderer.update(data);
+    }
+
+    fn update_layer<'a, T, U>(&mut self, layer: usize, data: T)
+    where
+        T: Iterator<Item = U>,
+        U: Into<&'a SpriteCell>,
+    {
+        self.renderer.update_layer(layer, data);
+    }
+
+    fn layer_count(&self) -> usize {
+        self.renderer.layer_count()
+    }
+
+    fn set_layer_count(&mut self, count: usize) {
+        self.renderer.set_layer_count(count);
+    }
+
+    fn get_fps(&self) -> f32 {
+        self.renderer.get_fps()
+    }
+
+    fn dimensions(&self) -> (u32, u32) {
+        self.renderer.dimensions()
+    }
+
+    fn set_camera(&mut self, camera: Camera, transition: std::time::Duration) {
+        self.renderer.set_camera(camera, transition);
+    }
+
+    fn camera(&self) -> Camera {
+        self.renderer.camera()
+    }
+
+    fn set_post_process_uniforms<T: bytemuck::Pod>(&mut self, pass: usize, uniforms: T) {
+        self.renderer.set_post_process_uniforms(pass, uniforms);
+    }
+
+    fn set_post_process_enabled(&mut self, pass: usize, enabled: bool) {
+        self.renderer.set_post_process_enabled(pass, enabled);
+    }
+
+    fn set_sprite_texture(&mut self, sprite_texture: &SpriteTexture) {
+        self.renderer.set_sprite_texture(sprite_texture);
+    }
+
+    fn add_animation(&mut self, animation: &Animation) -> AnimationId {
+        self.renderer.add_animation(animation)
+    }
+
+    fn add_palette_cycle(&mut self, cycle: &PaletteCycle) -> PaletteCycleId {
+        self.renderer.add_palette_cycle(cycle)
+    }
+
+    fn frame_stats(&self) -> Option<&FrameStats> {
+        self.renderer.frame_stats()
+    }
+
+    fn set_stats_overlay(&mut self, visible: bool) {
+        self.renderer.set_stats_overlay(visible);
+    }
+
+    fn set_gpu_timing(&mut self, enabled: bool) {
+        self.renderer.set_gpu_timing(enabled);
+    }
+}
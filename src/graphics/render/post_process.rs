@@ -0,0 +1,467 @@
+// Copyright 2018 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{catch_validation_errors, create_screen_texture_bind_group, RenderError, QUAD_INDICES};
+use wgpu::util::DeviceExt;
+
+/// Declarations shared by all screen shaders; see `shader/screen_common.wgsl`.
+const SCREEN_COMMON_SOURCE: &str = include_str!("shader/screen_common.wgsl");
+
+/// The default screen fragment shader, which just samples the render target.
+pub(crate) const DEFAULT_SCREEN_SHADER: &str = include_str!("shader/screen.wgsl");
+
+/// A full-screen shader pass that runs after the sprite grid has been scaled to the window, e.g.
+/// to add scanlines or bloom. Passes run in the order they are added, each reading the output of
+/// the one before.
+///
+/// The source is WGSL for a fragment shader with the entry point `fs_main`. It is compiled
+/// together with the declarations in `shader/screen_common.wgsl`, which provide:
+///
+///   * `screen_texture` and `screen_sampler` (group 0): the output of the previous pass.
+///   * `screen_globals` (group 1): the output size, where the grid is drawn in it, the frame
+///     counter and elapsed time.
+///   * The `ScreenVertexOutput` struct, which `fs_main` takes as input. Its `uv` field is the
+///     position in `screen_texture`.
+///
+/// The pass's own uniforms, if any, are bound to `@group(2) @binding(0)`. Passes created with
+/// `with_history()` can also read their own output from the previous frame through
+/// `history_texture` and `history_sampler` (group 3).
+///
+/// See `Effect` for some ready-made passes.
+#[derive(Clone, Debug)]
+pub struct PostProcessPass {
+    pub(crate) source: String,
+    pub(crate) uniforms: Vec<u8>,
+    pub(crate) uses_history: bool,
+    pub(crate) enabled: bool,
+}
+
+impl PostProcessPass {
+    /// Create a new pass from WGSL source.
+    pub fn new<S: Into<String>>(source: S) -> Self {
+        PostProcessPass {
+            source: source.into(),
+            uniforms: vec![],
+            uses_history: false,
+            enabled: true,
+        }
+    }
+
+    /// Set the initial value of the pass's uniforms. The layout of `T` must match the uniform
+    /// struct declared in the shader. They can be changed later with
+    /// `RenderInterface::set_post_process_uniforms()`.
+    #[must_use]
+    pub fn with_uniforms<T: bytemuck::Pod>(mut self, uniforms: T) -> Self {
+        self.uniforms = bytemuck::bytes_of(&uniforms).to_vec();
+
+        self
+    }
+
+    /// Give the pass access to its output from the previous frame, e.g. for motion blur or
+    /// phosphor persistence. This costs two extra textures the size of the window.
+    #[must_use]
+    pub fn with_history(mut self) -> Self {
+        self.uses_history = true;
+
+        self
+    }
+
+    /// Set whether the pass starts out enabled (the default). Passes can be turned on and off
+    /// later with `RenderInterface::set_post_process_enabled()`.
+    #[must_use]
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+
+        self
+    }
+}
+
+/// Pad uniform data to a multiple of 16 bytes (and at least 16), which satisfies both the copy
+/// alignment and WGSL's uniform struct size rules.
+fn padded_uniforms(data: &[u8]) -> Vec<u8> {
+    let mut padded = data.to_vec();
+    padded.resize(data.len().max(1).div_ceil(16) * 16, 0);
+    padded
+}
+
+/// Compile a screen shader and create its pipeline. `source` is the fragment shader; it is
+/// appended to the shared declarations, which also provide the vertex shader `vertex_entry`.
+pub(crate) fn create_screen_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    label: &str,
+    source: &str,
+    vertex_entry: &str,
+    format: wgpu::TextureFormat,
+) -> Result<wgpu::RenderPipeline, RenderError> {
+    catch_validation_errors(device, label, || {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(
+                format!("{}\n{}", SCREEN_COMMON_SOURCE, source).into(),
+            ),
+        });
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: vertex_entry,
+                buffers: &[super::Vertex::layout()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: "fs_main",
+                targets: &[Some(format.into())],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    })
+}
+
+/// An intermediate texture that passes render into and read from.
+struct Target {
+    view: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Target {
+    fn new(
+        device: &wgpu::Device,
+        size: (u32, u32),
+        format: wgpu::TextureFormat,
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+    ) -> Self {
+        let view = device
+            .create_texture(&wgpu::TextureDescriptor {
+                size: wgpu::Extent3d {
+                    width: size.0.max(1),
+                    height: size.1.max(1),
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                label: Some("Post-process texture"),
+            })
+            .create_view(&Default::default());
+        let bind_group =
+            create_screen_texture_bind_group(device, texture_bind_group_layout, &view, sampler);
+        Target { view, bind_group }
+    }
+}
+
+/// A compiled post-processing pass.
+struct Stage {
+    pipeline: wgpu::RenderPipeline,
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+    uniform_size: usize,
+    enabled: bool,
+    uses_history: bool,
+    // For passes that use history, the pass renders into these on alternate frames, reading the
+    // other one as `history_texture`.
+    history: Vec<Target>,
+}
+
+/// Draw a full-screen quad into `view`.
+fn draw_pass(
+    encoder: &mut wgpu::CommandEncoder,
+    pipeline: &wgpu::RenderPipeline,
+    bind_groups: &[&wgpu::BindGroup],
+    vertex_buffer: &wgpu::Buffer,
+    index_buffer: &wgpu::Buffer,
+    view: &wgpu::TextureView,
+) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("Post-process pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: true,
+            },
+        })],
+        depth_stencil_attachment: None,
+    });
+    render_pass.set_pipeline(pipeline);
+    for (i, bind_group) in bind_groups.iter().enumerate() {
+        render_pass.set_bind_group(i as u32, bind_group, &[]);
+    }
+    render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+    render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+    render_pass.draw_indexed(0..QUAD_INDICES.len() as _, 0, 0..1);
+}
+
+/// The GPU side of a chain of `PostProcessPass`es.
+pub(crate) struct PostProcessChain {
+    pipeline_layout: wgpu::PipelineLayout,
+    history_pipeline_layout: wgpu::PipelineLayout,
+    uniform_bind_group_layout: wgpu::BindGroupLayout,
+    // Copies a texture to the output unchanged; needed when the last pass renders into its
+    // history texture.
+    blit_pipeline: wgpu::RenderPipeline,
+    format: wgpu::TextureFormat,
+    stages: Vec<Stage>,
+    // Passes alternate between reading from one of these and writing to the other. The last pass
+    // writes to the output instead.
+    targets: Vec<Target>,
+    target_size: (u32, u32),
+    frame_counter: usize,
+}
+
+impl PostProcessChain {
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        screen_texture_bind_group_layout: &wgpu::BindGroupLayout,
+        screen_uniform_bind_group_layout: &wgpu::BindGroupLayout,
+        format: wgpu::TextureFormat,
+    ) -> Result<Self, RenderError> {
+        let uniform_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Post-process uniform bind group layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Post-process pipeline layout"),
+            bind_group_layouts: &[
+                screen_texture_bind_group_layout,
+                screen_uniform_bind_group_layout,
+                &uniform_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+        let history_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Post-process pipeline layout with history"),
+                bind_group_layouts: &[
+                    screen_texture_bind_group_layout,
+                    screen_uniform_bind_group_layout,
+                    &uniform_bind_group_layout,
+                    screen_texture_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+        let blit_pipeline = create_screen_pipeline(
+            device,
+            &pipeline_layout,
+            "Post-process blit",
+            DEFAULT_SCREEN_SHADER,
+            "vs_post_process",
+            format,
+        )?;
+        Ok(PostProcessChain {
+            pipeline_layout,
+            history_pipeline_layout,
+            uniform_bind_group_layout,
+            blit_pipeline,
+            format,
+            stages: vec![],
+            targets: vec![],
+            target_size: (0, 0),
+            frame_counter: 0,
+        })
+    }
+
+    /// Whether any passes are enabled. If not, the screen pass should render straight to the
+    /// output.
+    pub(crate) fn is_active(&self) -> bool {
+        self.stages.iter().any(|s| s.enabled)
+    }
+
+    /// Compile `pass` and add it to the end of the chain.
+    pub(crate) fn push(
+        &mut self,
+        device: &wgpu::Device,
+        pass: &PostProcessPass,
+    ) -> Result<(), RenderError> {
+        let pipeline = create_screen_pipeline(
+            device,
+            if pass.uses_history {
+                &self.history_pipeline_layout
+            } else {
+                &self.pipeline_layout
+            },
+            &format!("Post-process pass {}", self.stages.len()),
+            &pass.source,
+            "vs_post_process",
+            self.format,
+        )?;
+        let uniforms = padded_uniforms(&pass.uniforms);
+        let (uniform_buffer, uniform_bind_group) = self.create_uniforms(device, &uniforms);
+        self.stages.push(Stage {
+            pipeline,
+            uniform_buffer,
+            uniform_bind_group,
+            uniform_size: uniforms.len(),
+            enabled: pass.enabled,
+            uses_history: pass.uses_history,
+            history: vec![],
+        });
+        // Make sure the new pass gets its textures.
+        self.targets.clear();
+        Ok(())
+    }
+
+    fn create_uniforms(
+        &self,
+        device: &wgpu::Device,
+        data: &[u8],
+    ) -> (wgpu::Buffer, wgpu::BindGroup) {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Post-process uniform buffer"),
+            contents: data,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Post-process uniform bind group"),
+            layout: &self.uniform_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+        (buffer, bind_group)
+    }
+
+    /// Replace the uniforms of pass `index`. Does nothing if there is no such pass.
+    pub(crate) fn set_uniforms(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        index: usize,
+        data: &[u8],
+    ) {
+        if index >= self.stages.len() {
+            return;
+        }
+        let data = padded_uniforms(data);
+        if self.stages[index].uniform_size == data.len() {
+            queue.write_buffer(&self.stages[index].uniform_buffer, 0, &data);
+        } else {
+            let (buffer, bind_group) = self.create_uniforms(device, &data);
+            let stage = &mut self.stages[index];
+            stage.uniform_buffer = buffer;
+            stage.uniform_bind_group = bind_group;
+            stage.uniform_size = data.len();
+        }
+    }
+
+    /// Enable or disable pass `index`. Does nothing if there is no such pass.
+    pub(crate) fn set_enabled(&mut self, index: usize, enabled: bool) {
+        if let Some(stage) = self.stages.get_mut(index) {
+            stage.enabled = enabled;
+        }
+    }
+
+    /// Make sure the intermediate and history textures exist and are `size` pixels, and return
+    /// the texture the screen pass should render into.
+    pub(crate) fn input_view(
+        &mut self,
+        device: &wgpu::Device,
+        size: (u32, u32),
+        texture_bind_group_layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+    ) -> &wgpu::TextureView {
+        if self.target_size != size || self.targets.is_empty() {
+            self.target_size = size;
+            let format = self.format;
+            let target = || Target::new(device, size, format, texture_bind_group_layout, sampler);
+            self.targets = vec![target(), target()];
+            for stage in self.stages.iter_mut().filter(|s| s.uses_history) {
+                stage.history = vec![target(), target()];
+            }
+        }
+        &self.targets[0].view
+    }
+
+    /// Record the enabled passes. The first reads the texture returned by `input_view()`, and the
+    /// last writes to `output`.
+    pub(crate) fn encode(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        screen_uniform_bind_group: &wgpu::BindGroup,
+        vertex_buffer: &wgpu::Buffer,
+        index_buffer: &wgpu::Buffer,
+        output: &wgpu::TextureView,
+    ) {
+        let parity = self.frame_counter % 2;
+        self.frame_counter += 1;
+
+        let stages: Vec<&Stage> = self.stages.iter().filter(|s| s.enabled).collect();
+        let mut source = &self.targets[0];
+        let mut source_index = Some(0);
+        for (i, stage) in stages.iter().enumerate() {
+            let mut bind_groups = vec![
+                &source.bind_group,
+                screen_uniform_bind_group,
+                &stage.uniform_bind_group,
+            ];
+            let destination = if stage.uses_history {
+                bind_groups.push(&stage.history[1 - parity].bind_group);
+                source_index = None;
+                Some(&stage.history[parity])
+            } else if i + 1 == stages.len() {
+                None
+            } else {
+                let index = if source_index == Some(0) { 1 } else { 0 };
+                source_index = Some(index);
+                Some(&self.targets[index])
+            };
+            draw_pass(
+                encoder,
+                &stage.pipeline,
+                &bind_groups,
+                vertex_buffer,
+                index_buffer,
+                destination.map_or(output, |t| &t.view),
+            );
+            if let Some(destination) = destination {
+                source = destination;
+            }
+        }
+        // The blit doesn't use uniforms, but its pipeline layout requires a bind group for them.
+        if let Some(last) = stages.last().filter(|s| s.uses_history) {
+            draw_pass(
+                encoder,
+                &self.blit_pipeline,
+                &[
+                    &source.bind_group,
+                    screen_uniform_bind_group,
+                    &last.uniform_bind_group,
+                ],
+                vertex_buffer,
+                index_buffer,
+                output,
+            );
+        }
+    }
+}
@@ -0,0 +1,183 @@
+// Declarations shared by all cell shaders. User-supplied cell shaders are compiled after this
+// file, so they can use everything declared here.
+
+struct CellVertexInput {
+  @location(0) pos: vec2<f32>,
+  @location(1) uv: vec2<f32>,
+  @location(2) translate: vec2<f32>,
+  @location(3) cell_coords: vec2<u32>,
+  @location(4) sprite: u32,
+  @location(5) index: u32,
+  @location(6) transparent_mask: u32,
+  @location(7) user_data: u32,
+  @location(8) animation: u32,
+  @location(9) animation_start: f32,
+  @location(10) palette_cycle: u32,
+}
+
+struct CellVertexOutput {
+  @builtin(position) pos: vec4<f32>,
+  @location(0) index: u32,
+  @location(1) uv: vec2<f32>,
+  @location(2) @interpolate(flat) cell_coords: vec2<f32>,
+  @location(3) @interpolate(flat) transparent_mask: u32,
+  @location(4) @interpolate(flat) sprite: u32,
+  @location(5) @interpolate(flat) user_data: u32,
+  // Position within the cell, from (0, 0) at the top left to (1, 1) at the bottom right.
+  @location(6) cell_uv: vec2<f32>,
+  @location(7) @interpolate(flat) palette_cycle: u32,
+}
+
+struct CellGlobals {
+  screen_size_in_sprites: vec2<u32>,
+  sprite_map_dimensions: vec2<u32>,
+  sprite_texture_dimensions: vec2<u32>,
+  sprite_dimensions: vec2<u32>,
+  palette_texture_dimensions: vec2<u32>,
+  elapsed_time: f32,
+  frame_counter: u32,
+}
+
+struct Animation {
+  first_frame: u32,
+  frame_count: u32,
+  // 1 if the animation stops on its last frame, 0 if it loops.
+  once: u32,
+  duration: f32,
+}
+
+struct AnimationFrame {
+  sprite: u32,
+  duration: f32,
+}
+
+struct PaletteCycle {
+  // 0 rotates palette indices `first..first + count`, 1 blinks to the palette starting at
+  // `first_color`, and 2 fades palette index `first` through the `color_count` colors starting at
+  // `first_color`.
+  kind: u32,
+  first: u32,
+  count: u32,
+  period: f32,
+  first_color: u32,
+  color_count: u32,
+}
+
+@group(0) @binding(0) var<uniform> cell_globals: CellGlobals;
+@group(0) @binding(1) var<storage, read> animations: array<Animation>;
+@group(0) @binding(2) var<storage, read> animation_frames: array<AnimationFrame>;
+@group(0) @binding(3) var<storage, read> palette_cycles: array<PaletteCycle>;
+@group(0) @binding(4) var<storage, read> palette_cycle_colors: array<vec4<f32>>;
+
+// Get the sprite to show in a cell. `animation` is 0 for cells that aren't animated, and
+// otherwise one more than the index of the animation. Mirrors `Animation::sprite_at()`.
+fn animated_sprite(sprite: u32, animation: u32, start: f32) -> u32 {
+    if (animation == 0u) {
+        return sprite;
+    }
+    let a = animations[animation - 1u];
+    if (a.frame_count == 0u) {
+        return sprite;
+    }
+    var t = max(cell_globals.elapsed_time - start, 0.0);
+    if (a.duration <= 0.0) {
+        t = 0.0;
+    } else if (a.once == 0u) {
+        t = t % a.duration;
+    }
+    for (var i = 0u; i < a.frame_count; i = i + 1u) {
+        let frame = animation_frames[a.first_frame + i];
+        if (t < frame.duration) {
+            return frame.sprite;
+        }
+        t = t - frame.duration;
+    }
+    return animation_frames[a.first_frame + a.frame_count - 1u].sprite;
+}
+
+@vertex
+fn vs_main(in: CellVertexInput) -> CellVertexOutput {
+    var out: CellVertexOutput;
+    let sprite = animated_sprite(in.sprite, in.animation, in.animation_start);
+    var sprite_offset: vec2<f32> = vec2<f32>(
+        f32(sprite % cell_globals.sprite_map_dimensions.x),
+        f32(sprite / cell_globals.sprite_map_dimensions.x));
+
+    out.uv =
+        in.uv / vec2<f32>(cell_globals.sprite_map_dimensions)
+        + sprite_offset / vec2<f32>(cell_globals.sprite_map_dimensions);
+
+    out.index = in.index;
+    out.cell_coords = vec2<f32>(in.cell_coords);
+    out.transparent_mask = in.transparent_mask;
+    out.sprite = sprite;
+    out.user_data = in.user_data;
+    out.cell_uv = in.uv;
+    out.palette_cycle = in.palette_cycle;
+
+    out.pos = vec4<f32>(
+        in.pos * 2.0 / vec2<f32>(cell_globals.screen_size_in_sprites) + in.translate,
+        0.0,
+        1.0);
+
+    return out;
+}
+
+@group(1) @binding(0) var sprite_texture: texture_2d<u32>;
+@group(1) @binding(1) var palette_texture: texture_3d<f32>;
+
+// Everything a cell shader knows about the pixel being drawn.
+struct Cell {
+  // Column and row of the cell in the grid.
+  coords: vec2<u32>,
+  // Sprite index (the current frame, for animated cells) and user data from the cell's
+  // `SpriteCell`.
+  sprite: u32,
+  user_data: u32,
+  // Position within the cell, from (0, 0) at the top left to (1, 1) at the bottom right.
+  uv: vec2<f32>,
+  // Palette index of the sprite pixel (0-15).
+  palette_index: u32,
+  // 0 if the cell has no palette cycle, and otherwise one more than the index of its cycle.
+  palette_cycle: u32,
+}
+
+// Get the palette index (0-15) of pixel `pixel` of sprite `sprite`.
+fn sprite_pixel(sprite: u32, pixel: vec2<u32>) -> u32 {
+    let sprite_origin = vec2<u32>(
+        sprite % cell_globals.sprite_map_dimensions.x,
+        sprite / cell_globals.sprite_map_dimensions.x) * cell_globals.sprite_dimensions;
+    let t = textureLoad(sprite_texture, vec2<i32>(sprite_origin + pixel), 0);
+    return clamp(t.x, 0u, 15u);
+}
+
+// Get color `index` (0-15) of the cell's palette, with its palette cycle applied. Mirrors
+// `PaletteCycle::palette_at()`.
+fn palette_color(cell: Cell, index: u32) -> vec4<f32> {
+    var i = index;
+    if (cell.palette_cycle != 0u) {
+        let c = palette_cycles[cell.palette_cycle - 1u];
+        var phase = 0.0;
+        if (c.period > 0.0) {
+            phase = cell_globals.elapsed_time / c.period;
+        }
+        if (c.kind == 0u) {
+            if (i >= c.first && i < c.first + c.count) {
+                i = c.first + (i - c.first + u32(phase) % c.count) % c.count;
+            }
+        } else if (c.kind == 1u) {
+            if (u32(phase) % 2u == 1u) {
+                return palette_cycle_colors[c.first_color + i];
+            }
+        } else if (i == c.first && c.color_count > 0u) {
+            let position = fract(phase) * f32(c.color_count);
+            let k = u32(position) % c.color_count;
+            return mix(palette_cycle_colors[c.first_color + k],
+                       palette_cycle_colors[c.first_color + (k + 1u) % c.color_count],
+                       fract(position));
+        }
+    }
+    return textureLoad(palette_texture,
+                       vec3<i32>(i32(i), i32(cell.coords.x), i32(cell.coords.y)),
+                       0);
+}
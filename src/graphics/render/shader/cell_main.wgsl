@@ -0,0 +1,25 @@
+// Entry point for all cell shaders; calls the `shade_cell()` function provided by the cell
+// shader.
+@fragment
+fn fs_main(in: CellVertexOutput) -> @location(0) vec4<f32> {
+    // The "color" here is the index into the palette for this cell (0-15).
+    var t: vec4<u32> = textureLoad(
+        sprite_texture,
+        vec2<i32>(i32(floor(in.uv.x * f32(cell_globals.sprite_texture_dimensions.x))),
+                  i32(floor(in.uv.y * f32(cell_globals.sprite_texture_dimensions.y)))),
+        0);
+    var cell: Cell;
+    cell.coords = vec2<u32>(in.cell_coords);
+    cell.sprite = in.sprite;
+    cell.user_data = in.user_data;
+    cell.uv = in.cell_uv;
+    cell.palette_index = clamp(t.x, 0u, 15u);
+    cell.palette_cycle = in.palette_cycle;
+    var color: vec4<f32> = shade_cell(cell);
+    // Keyed-out palette indices (or fully transparent cells) are blended away entirely so the
+    // layer below shows through.
+    if (((in.transparent_mask >> cell.palette_index) & 1u) == 1u) {
+        color.a = 0.0;
+    }
+    return color;
+}
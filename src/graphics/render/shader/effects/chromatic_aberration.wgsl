@@ -0,0 +1,15 @@
+struct ChromaticAberration {
+    offset: f32,
+}
+
+@group(2) @binding(0) var<uniform> params: ChromaticAberration;
+
+@fragment
+fn fs_main(in: ScreenVertexOutput) -> @location(0) vec4<f32> {
+  // Red and blue are shifted in opposite directions, away from the center of the screen.
+  let shift = (in.uv - 0.5) * 2.0 * params.offset / screen_globals.screen_size;
+  let r = textureSample(screen_texture, screen_sampler, in.uv + shift).r;
+  let ga = textureSample(screen_texture, screen_sampler, in.uv).ga;
+  let b = textureSample(screen_texture, screen_sampler, in.uv - shift).b;
+  return vec4<f32>(r, ga.x, b, ga.y);
+}
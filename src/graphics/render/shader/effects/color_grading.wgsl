@@ -0,0 +1,23 @@
+struct ColorGrading {
+    brightness: f32,
+    contrast: f32,
+    saturation: f32,
+    gamma: f32,
+    tint_r: f32,
+    tint_g: f32,
+    tint_b: f32,
+}
+
+@group(2) @binding(0) var<uniform> params: ColorGrading;
+
+@fragment
+fn fs_main(in: ScreenVertexOutput) -> @location(0) vec4<f32> {
+  let color = textureSample(screen_texture, screen_sampler, in.uv);
+  var c = color.rgb * params.brightness;
+  c = (c - 0.5) * params.contrast + 0.5;
+  let luma = dot(c, vec3<f32>(0.2126, 0.7152, 0.0722));
+  c = mix(vec3<f32>(luma), c, params.saturation);
+  c = c * vec3<f32>(params.tint_r, params.tint_g, params.tint_b);
+  c = pow(clamp(c, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(1.0 / params.gamma));
+  return vec4<f32>(c, color.a);
+}
@@ -0,0 +1,18 @@
+struct Curvature {
+    amount: f32,
+}
+
+@group(2) @binding(0) var<uniform> params: Curvature;
+
+@fragment
+fn fs_main(in: ScreenVertexOutput) -> @location(0) vec4<f32> {
+  // Barrel distortion: points are pushed outwards more the further they are from the center.
+  var p = in.uv * 2.0 - 1.0;
+  p = p * (1.0 + params.amount * p.yx * p.yx);
+  let uv = p * 0.5 + 0.5;
+  let color = textureSample(screen_texture, screen_sampler, uv);
+  if (any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0))) {
+    return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+  }
+  return color;
+}
@@ -0,0 +1,12 @@
+struct PhosphorPersistence {
+    decay: f32,
+}
+
+@group(2) @binding(0) var<uniform> params: PhosphorPersistence;
+
+@fragment
+fn fs_main(in: ScreenVertexOutput) -> @location(0) vec4<f32> {
+  let color = textureSample(screen_texture, screen_sampler, in.uv);
+  let previous = textureSample(history_texture, history_sampler, in.uv);
+  return vec4<f32>(max(color.rgb, previous.rgb * params.decay), color.a);
+}
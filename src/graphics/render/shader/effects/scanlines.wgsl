@@ -0,0 +1,17 @@
+struct Scanlines {
+    intensity: f32,
+}
+
+@group(2) @binding(0) var<uniform> params: Scanlines;
+
+@fragment
+fn fs_main(in: ScreenVertexOutput) -> @location(0) vec4<f32> {
+  let color = textureSample(screen_texture, screen_sampler, in.uv);
+  // Position in rows of grid pixels, taking the viewport and camera into account, so each row
+  // of pixels gets one scanline regardless of how much the grid is scaled.
+  let v = (in.pos.y - screen_globals.viewport.y) / screen_globals.viewport.w;
+  let row = (v * screen_globals.uv_scale.y + screen_globals.uv_offset.y)
+      * screen_globals.source_size.y;
+  let brightness = 1.0 - params.intensity * (1.0 - sin(fract(row) * 3.14159265));
+  return vec4<f32>(color.rgb * brightness, color.a);
+}
@@ -0,0 +1,14 @@
+struct Vignette {
+    intensity: f32,
+}
+
+@group(2) @binding(0) var<uniform> params: Vignette;
+
+@fragment
+fn fs_main(in: ScreenVertexOutput) -> @location(0) vec4<f32> {
+  let color = textureSample(screen_texture, screen_sampler, in.uv);
+  let d = in.uv - 0.5;
+  // 1 in the center, falling to `1 - intensity` in the corners.
+  let brightness = 1.0 - params.intensity * dot(d, d) * 2.0;
+  return vec4<f32>(color.rgb * brightness, color.a);
+}
@@ -0,0 +1,53 @@
+// Declarations shared by the screen shader and post-processing passes. User-supplied fragment
+// shaders are compiled together with this file, so they can use everything declared here.
+
+struct ScreenGlobals {
+    screen_size: vec2<f32>,
+    scale_factor: vec2<f32>,
+    frame_counter: u32,
+    elapsed_time: f32,
+    translate: vec2<f32>,
+    uv_scale: vec2<f32>,
+    uv_offset: vec2<f32>,
+    // Where the grid is drawn in the output: x, y, width, height, in pixels.
+    viewport: vec4<f32>,
+    // Size of the unscaled grid, in pixels.
+    source_size: vec2<f32>,
+}
+
+struct ScreenVertexOutput {
+  @builtin(position) pos: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+}
+
+struct ScreenVertexInput {
+  @location(0) pos: vec2<f32>,
+  @location(1) uv: vec2<f32>,
+}
+
+@group(0) @binding(0) var screen_texture: texture_2d<f32>;
+@group(0) @binding(1) var screen_sampler: sampler;
+
+@group(1) @binding(0) var<uniform> screen_globals: ScreenGlobals;
+
+// Only available to post-processing passes that use history.
+@group(3) @binding(0) var history_texture: texture_2d<f32>;
+@group(3) @binding(1) var history_sampler: sampler;
+
+// Scales the render target into the viewport, applying the camera.
+@vertex
+fn vs_main(in: ScreenVertexInput) -> ScreenVertexOutput {
+  var out: ScreenVertexOutput;
+  out.uv = in.uv * screen_globals.uv_scale + screen_globals.uv_offset;
+  out.pos = vec4<f32>(in.pos * screen_globals.scale_factor + screen_globals.translate, 0.0, 1.0);
+  return out;
+}
+
+// Covers the whole output; used by post-processing passes.
+@vertex
+fn vs_post_process(in: ScreenVertexInput) -> ScreenVertexOutput {
+  var out: ScreenVertexOutput;
+  out.uv = in.uv;
+  out.pos = vec4<f32>(in.pos, 0.0, 1.0);
+  return out;
+}
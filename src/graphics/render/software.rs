@@ -0,0 +1,293 @@
+// Copyright 2018 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use super::{OffscreenRenderer, RenderError, RenderInterface};
+use crate::graphics::animation::{Animation, AnimationId, PaletteCycle, PaletteCycleId};
+use crate::graphics::drawing::SpriteCell;
+use crate::resources::color::Palette;
+use crate::resources::sprite::SpriteTexture;
+
+// Color the render target is cleared to before any layers are drawn. This matches the clear color
+// of the sprite cell pass in the wgpu renderer (0.5, 0.1, 0.1) after conversion to 8 bits.
+const CLEAR_COLOR: [u8; 4] = [128, 26, 26, 255];
+
+/// A renderer that rasterizes sprite cells entirely on the CPU. It produces the same output as
+/// `HeadlessRenderer` without requiring a wgpu adapter, which makes it suitable for tests,
+/// thumbnails and server-side screenshots.
+///
+/// Animations are timed from the wall clock between frames, like the other renderers. For
+/// reproducible output, use `with_fixed_frame_time()`.
+pub struct SoftwareRenderer {
+    // Width & height, in sprites.
+    width: u32,
+    height: u32,
+
+    sprite_texture_width: usize,
+    sprite_texture_height: usize,
+    sprite_width: usize,
+    sprite_height: usize,
+    sprite_pixels: Box<[u8]>,
+
+    // Layers are drawn in order, so the last layer is on top.
+    layers: Vec<Layer>,
+    animations: Vec<Animation>,
+    palette_cycles: Vec<PaletteCycle>,
+
+    last_render_time: time::OffsetDateTime,
+    // Time since the first frame, used to play animations.
+    elapsed_time: Duration,
+    // If set, the amount `elapsed_time` advances by each frame, instead of the wall clock time.
+    fixed_frame_time: Option<Duration>,
+    fps: f32,
+}
+
+struct Layer {
+    cells: Box<[SpriteCell]>,
+    // Elapsed time when each cell's animation was set.
+    animation_starts: Box<[Duration]>,
+}
+
+impl SoftwareRenderer {
+    /// Create a new `SoftwareRenderer` with the given width and height (measured in sprites, not
+    /// pixels).
+    pub fn new(width: u32, height: u32, sprite_texture: &SpriteTexture) -> Self {
+        SoftwareRenderer {
+            width,
+            height,
+            sprite_texture_width: sprite_texture.width(),
+            sprite_texture_height: sprite_texture.height(),
+            sprite_width: sprite_texture.sprite_width(),
+            sprite_height: sprite_texture.sprite_height(),
+            sprite_pixels: Box::from(sprite_texture.pixels()),
+            layers: vec![Self::new_layer(width, height, true)],
+            animations: vec![],
+            palette_cycles: vec![],
+            last_render_time: time::OffsetDateTime::now_utc(),
+            elapsed_time: Duration::ZERO,
+            fixed_frame_time: None,
+            fps: 0.0,
+        }
+    }
+
+    /// Advance the clock used for animations by exactly `frame_time` after each frame, instead of
+    /// by the time that actually passed. This makes the output of animated cells reproducible,
+    /// e.g. in tests run with `TestHarness`.
+    #[must_use]
+    pub fn with_fixed_frame_time(mut self, frame_time: Duration) -> Self {
+        self.fixed_frame_time = Some(frame_time);
+
+        self
+    }
+
+    fn new_layer(width: u32, height: u32, opaque: bool) -> Layer {
+        let cell = SpriteCell {
+            palette: Palette::mono([255, 255, 255]),
+            transparent: !opaque,
+            ..Default::default()
+        };
+        let len = (width * height) as usize;
+        Layer {
+            cells: vec![cell; len].into_boxed_slice(),
+            animation_starts: vec![Duration::ZERO; len].into_boxed_slice(),
+        }
+    }
+
+    /// Change the size of the sprite grid (and with it, the rendered image). All layers are reset
+    /// to their initial contents.
+    pub fn resize_grid(&mut self, width: u32, height: u32) {
+        let (width, height) = (width.max(1), height.max(1));
+        self.width = width;
+        self.height = height;
+        self.layers = (0..self.layers.len())
+            .map(|i| Self::new_layer(width, height, i == 0))
+            .collect();
+    }
+
+    /// Get the size of the rendered image, in pixels.
+    pub fn output_size(&self) -> (u32, u32) {
+        (
+            self.width * self.sprite_width as u32,
+            self.height * self.sprite_height as u32,
+        )
+    }
+
+    /// The sprite shown in `cell`, which started its animation (if any) at `animation_start`.
+    fn current_sprite(&self, cell: &SpriteCell, animation_start: Duration) -> u32 {
+        cell.animation
+            .and_then(|id| self.animations.get(id.0 as usize))
+            .and_then(|a| a.sprite_at(self.elapsed_time.saturating_sub(animation_start)))
+            .unwrap_or(cell.sprite)
+    }
+
+    /// The palette of `cell`, after applying its palette cycle (if any).
+    fn current_palette(&self, cell: &SpriteCell) -> Palette {
+        cell.palette_cycle
+            .and_then(|id| self.palette_cycles.get(id.0 as usize))
+            .map_or(cell.palette, |c| {
+                c.palette_at(cell.palette, self.elapsed_time)
+            })
+    }
+
+    /// Look up the palette index of a pixel in the sprite texture. Pixels outside of the texture
+    /// are treated as palette index 0.
+    fn sprite_texel(&self, sprite: u32, x: usize, y: usize) -> usize {
+        let sprites_wide = self.sprite_texture_width / self.sprite_width;
+        let tx = (sprite as usize % sprites_wide) * self.sprite_width + x;
+        let ty = (sprite as usize / sprites_wide) * self.sprite_height + y;
+        if tx < self.sprite_texture_width && ty < self.sprite_texture_height {
+            (self.sprite_pixels[ty * self.sprite_texture_width + tx] as usize).min(15)
+        } else {
+            0
+        }
+    }
+
+    /// Render one frame and return it as RGBA8 pixels, in row-major order starting at the top
+    /// left.
+    pub fn render_frame(&mut self) -> Box<[u8]> {
+        let (output_width, output_height) = self.output_size();
+        let mut output = CLEAR_COLOR
+            .repeat((output_width * output_height) as usize)
+            .into_boxed_slice();
+
+        for layer in self.layers.iter() {
+            for (i, (cell, &start)) in layer
+                .cells
+                .iter()
+                .zip(layer.animation_starts.iter())
+                .enumerate()
+            {
+                if cell.transparent {
+                    continue;
+                }
+                let sprite = self.current_sprite(cell, start);
+                let palette = self.current_palette(cell);
+                let cell_x = (i % self.width as usize) * self.sprite_width;
+                let cell_y = (i / self.width as usize) * self.sprite_height;
+                for y in 0..self.sprite_height {
+                    let row = (cell_y + y) * output_width as usize;
+                    for x in 0..self.sprite_width {
+                        let p = self.sprite_texel(sprite, x, y);
+                        if cell.is_transparent_at(p) {
+                            continue;
+                        }
+                        let [r, g, b] = palette[p];
+                        let offset = (row + cell_x + x) * 4;
+                        output[offset..offset + 4].copy_from_slice(&[r, g, b, 255]);
+                    }
+                }
+            }
+        }
+
+        let t = time::OffsetDateTime::now_utc();
+        let dt = t - self.last_render_time;
+        let dt_micros = dt.whole_microseconds();
+        if dt_micros > 0 {
+            let new_fps = 1_000_000.0 / dt_micros as f32;
+            self.fps = 0.9 * self.fps + 0.1 * new_fps;
+        }
+        self.last_render_time = t;
+        self.elapsed_time += self
+            .fixed_frame_time
+            .unwrap_or_else(|| dt.try_into().unwrap_or(Duration::ZERO));
+
+        output
+    }
+}
+
+impl OffscreenRenderer for SoftwareRenderer {
+    fn output_size(&self) -> (u32, u32) {
+        SoftwareRenderer::output_size(self)
+    }
+
+    fn capture_frame(&mut self) -> Result<Box<[u8]>, RenderError> {
+        Ok(self.render_frame())
+    }
+
+    fn resize_grid(&mut self, width: u32, height: u32) {
+        SoftwareRenderer::resize_grid(self, width, height)
+    }
+}
+
+impl RenderInterface for SoftwareRenderer {
+    fn update<'a, T, U>(&mut self, data: T)
+    where
+        T: Iterator<Item = U>,
+        U: Into<&'a SpriteCell>,
+    {
+        self.update_layer(0, data);
+    }
+
+    fn update_layer<'a, T, U>(&mut self, layer: usize, data: T)
+    where
+        T: Iterator<Item = U>,
+        U: Into<&'a SpriteCell>,
+    {
+        let layer = &mut self.layers[layer];
+        for ((dst, start), src) in layer
+            .cells
+            .iter_mut()
+            .zip(layer.animation_starts.iter_mut())
+            .zip(data)
+        {
+            let src: &SpriteCell = src.into();
+            if src.animation != dst.animation {
+                *start = self.elapsed_time;
+            }
+            *dst = *src;
+        }
+    }
+
+    fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    fn set_layer_count(&mut self, count: usize) {
+        let count = count.max(1);
+        self.layers.truncate(count);
+        while self.layers.len() < count {
+            self.layers
+                .push(Self::new_layer(self.width, self.height, false));
+        }
+    }
+
+    fn get_fps(&self) -> f32 {
+        self.fps
+    }
+
+    fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn set_sprite_texture(&mut self, sprite_texture: &SpriteTexture) {
+        self.sprite_texture_width = sprite_texture.width();
+        self.sprite_texture_height = sprite_texture.height();
+        self.sprite_width = sprite_texture.sprite_width();
+        self.sprite_height = sprite_texture.sprite_height();
+        self.sprite_pixels = Box::from(sprite_texture.pixels());
+    }
+
+    fn add_animation(&mut self, animation: &Animation) -> AnimationId {
+        self.animations.push(animation.clone());
+
+        AnimationId(self.animations.len() as u32 - 1)
+    }
+
+    fn add_palette_cycle(&mut self, cycle: &PaletteCycle) -> PaletteCycleId {
+        self.palette_cycles.push(cycle.clone());
+
+        PaletteCycleId(self.palette_cycles.len() as u32 - 1)
+    }
+}
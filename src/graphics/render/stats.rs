@@ -0,0 +1,306 @@
+// Copyright 2018 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::graphics::drawing::SpriteCell;
+use crate::resources::color::Palette;
+
+/// Number of frames kept in each `TimingHistory`.
+pub const FRAME_HISTORY: usize = 240;
+
+/// A frame counts as dropped when it takes this many times longer than the median frame.
+const DROPPED_FRAME_FACTOR: f64 = 1.5;
+
+/// Frames needed in the history before any can count as dropped.
+const MIN_FRAMES_FOR_DROPS: usize = 10;
+
+/// Durations measured over the last `FRAME_HISTORY` frames, oldest first.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TimingHistory {
+    samples: VecDeque<Duration>,
+}
+
+impl TimingHistory {
+    pub(crate) fn record(&mut self, sample: Duration) {
+        if self.samples.len() == FRAME_HISTORY {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    /// All recorded samples, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = Duration> + '_ {
+        self.samples.iter().copied()
+    }
+
+    /// Number of recorded samples.
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Returns `true` iff nothing has been recorded yet.
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// The most recent sample.
+    pub fn last(&self) -> Option<Duration> {
+        self.samples.back().copied()
+    }
+
+    /// The shortest sample.
+    pub fn min(&self) -> Option<Duration> {
+        self.iter().min()
+    }
+
+    /// The longest sample.
+    pub fn max(&self) -> Option<Duration> {
+        self.iter().max()
+    }
+
+    /// The mean of the samples.
+    pub fn mean(&self) -> Option<Duration> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self.iter().sum::<Duration>() / self.len() as u32)
+        }
+    }
+
+    /// The `p`th percentile (0-100) of the samples, e.g. `percentile(99.0)` for a sense of the
+    /// worst frames without the odd outlier. Uses the nearest-rank method, so it is always one of
+    /// the samples.
+    pub fn percentile(&self, p: f64) -> Option<Duration> {
+        let mut sorted = self.iter().collect::<Vec<_>>();
+        sorted.sort_unstable();
+        let rank = (p.clamp(0.0, 100.0) / 100.0 * sorted.len() as f64).ceil() as usize;
+        sorted.get(rank.max(1) - 1).copied()
+    }
+}
+
+/// Timing information about recent frames, from `RenderInterface::frame_stats()`. Each history
+/// covers the last `FRAME_HISTORY` frames.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    /// Time from one frame to the next.
+    pub frame_time: TimingHistory,
+    /// CPU time spent in `Driver::process_frame()` each frame.
+    pub process_time: TimingHistory,
+    /// CPU time the renderer spent preparing and submitting each frame.
+    pub render_time: TimingHistory,
+    /// Time the GPU spent drawing each frame. Only recorded while enabled with
+    /// `RenderInterface::set_gpu_timing()`, and only on adapters that support timestamp queries.
+    pub gpu_time: TimingHistory,
+    /// Number of frames rendered.
+    pub frames: u64,
+    /// Number of frames that took more than one and a half times as long as the median frame at
+    /// the time, e.g. because they missed a vsync.
+    pub dropped_frames: u64,
+}
+
+impl FrameStats {
+    /// Frames per second, averaged over the frame time history.
+    pub fn fps(&self) -> f32 {
+        match self.frame_time.mean() {
+            Some(mean) if !mean.is_zero() => 1.0 / mean.as_secs_f32(),
+            _ => 0.0,
+        }
+    }
+
+    pub(crate) fn record_frame(&mut self, frame_time: Duration) {
+        if self.frame_time.len() >= MIN_FRAMES_FOR_DROPS {
+            if let Some(median) = self.frame_time.percentile(50.0) {
+                if frame_time > median.mul_f64(DROPPED_FRAME_FACTOR) {
+                    self.dropped_frames += 1;
+                }
+            }
+        }
+        self.frame_time.record(frame_time);
+        self.frames += 1;
+    }
+
+    /// The text shown by the statistics overlay.
+    pub(crate) fn overlay_lines(&self) -> [String; 2] {
+        fn ms(d: Option<Duration>) -> String {
+            d.map_or_else(
+                || "-".to_string(),
+                |d| format!("{:.2}", d.as_secs_f64() * 1000.0),
+            )
+        }
+        [
+            format!(
+                "{:.1} FPS {}ms p99 {}ms max {}ms",
+                self.fps(),
+                ms(self.frame_time.mean()),
+                ms(self.frame_time.percentile(99.0)),
+                ms(self.frame_time.max()),
+            ),
+            format!(
+                "CPU {}+{}ms GPU {}ms dropped {}",
+                ms(self.process_time.mean()),
+                ms(self.render_time.mean()),
+                ms(self.gpu_time.mean()),
+                self.dropped_frames,
+            ),
+        ]
+    }
+}
+
+/// Lay out `lines` at the top left of a `width` by `height` grid, as sprites from the sprite
+/// font. Everything else is transparent.
+pub(crate) fn overlay_cells(lines: &[String], width: usize, height: usize) -> Vec<SpriteCell> {
+    let mut cells = vec![
+        SpriteCell {
+            transparent: true,
+            ..Default::default()
+        };
+        width * height
+    ];
+    let palette = Palette::mono([0, 0, 0]).set(1, [255, 255, 85]);
+    for (y, line) in lines.iter().enumerate().take(height) {
+        for (x, c) in line.chars().enumerate().take(width) {
+            cells[y * width + x] = SpriteCell {
+                palette,
+                sprite: c as u32,
+                ..Default::default()
+            };
+        }
+    }
+    cells
+}
+
+/// Measures how long the GPU spends on each frame, using timestamp queries.
+pub(super) struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    buffer: wgpu::Buffer,
+    // Nanoseconds per timestamp tick.
+    period: f32,
+}
+
+impl GpuTimer {
+    /// Create a timer, or return `None` if the device doesn't support timestamp queries.
+    pub(super) fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+        Some(GpuTimer {
+            query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("Frame timer queries"),
+                ty: wgpu::QueryType::Timestamp,
+                count: 2,
+            }),
+            buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Frame timer buffer"),
+                size: 2 * std::mem::size_of::<u64>() as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            period: queue.get_timestamp_period(),
+        })
+    }
+
+    /// Record the start of the frame.
+    pub(super) fn begin(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.write_timestamp(&self.query_set, 0);
+    }
+
+    /// Record the end of the frame.
+    pub(super) fn end(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.write_timestamp(&self.query_set, 1);
+        encoder.resolve_query_set(&self.query_set, 0..2, &self.buffer, 0);
+    }
+
+    /// Wait for the frame to finish and return how long the GPU spent on it. This stalls until
+    /// the GPU is idle, which is why GPU timing is off by default.
+    pub(super) fn read(&self, device: &wgpu::Device) -> Option<Duration> {
+        let slice = self.buffer.slice(..);
+        let (tx, rx) = futures_intrusive::channel::shared::oneshot_channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            // If the receiver has gone there is nobody left to tell.
+            let _ = tx.send(result.is_ok());
+        });
+        device.poll(wgpu::Maintain::Wait);
+        if futures::executor::block_on(rx.receive()) != Some(true) {
+            return None;
+        }
+        let timestamps: [u64; 2] = bytemuck::pod_read_unaligned(&slice.get_mapped_range()[..]);
+        self.buffer.unmap();
+        let ticks = timestamps[1].saturating_sub(timestamps[0]);
+        Some(Duration::from_nanos(
+            (ticks as f64 * self.period as f64) as u64,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timing_history() {
+        use std::time::Duration;
+
+        let mut history = TimingHistory::default();
+        assert_eq!(history.mean(), None);
+        assert_eq!(history.percentile(50.0), None);
+        for ms in 1..=10 {
+            history.record(Duration::from_millis(ms));
+        }
+        assert_eq!(history.min(), Some(Duration::from_millis(1)));
+        assert_eq!(history.max(), Some(Duration::from_millis(10)));
+        assert_eq!(history.mean(), Some(Duration::from_micros(5500)));
+        assert_eq!(history.percentile(0.0), Some(Duration::from_millis(1)));
+        assert_eq!(history.percentile(50.0), Some(Duration::from_millis(5)));
+        assert_eq!(history.percentile(99.0), Some(Duration::from_millis(10)));
+
+        for _ in 0..FRAME_HISTORY {
+            history.record(Duration::from_millis(20));
+        }
+        assert_eq!(history.len(), FRAME_HISTORY);
+        assert_eq!(history.min(), Some(Duration::from_millis(20)));
+        assert_eq!(history.last(), Some(Duration::from_millis(20)));
+    }
+
+    #[test]
+    fn dropped_frames() {
+        use std::time::Duration;
+
+        let mut stats = FrameStats::default();
+        // A slow first frame can't count as dropped, since there is nothing to compare it with.
+        stats.record_frame(Duration::from_millis(100));
+        for _ in 0..20 {
+            stats.record_frame(Duration::from_millis(16));
+        }
+        stats.record_frame(Duration::from_millis(33));
+        stats.record_frame(Duration::from_millis(20));
+        assert_eq!(stats.frames, 23);
+        assert_eq!(stats.dropped_frames, 1);
+        assert!((stats.fps() - 23.0 / 0.473).abs() < 0.1);
+    }
+
+    #[test]
+    fn stats_overlay_cells() {
+        let cells = overlay_cells(&["abc".to_string(), "de".to_string()], 2, 3);
+        assert_eq!(
+            cells.iter().map(|c| c.sprite).collect::<Vec<_>>(),
+            ['a' as u32, 'b' as u32, 'd' as u32, 'e' as u32, 0, 0]
+        );
+        assert_eq!(
+            cells.iter().map(|c| c.transparent).collect::<Vec<_>>(),
+            [false, false, false, false, true, true]
+        );
+    }
+}
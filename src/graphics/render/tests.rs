@@ -21,6 +21,27 @@ use png;
 use super::*;
 use crate::resources::color::Palette;
 
+/// Load the test sprite texture.
+fn load_test_sprite_texture() -> SpriteTexture {
+    // TODO: get rid of this once the sprite-loading code is done.
+    let img = include_bytes!("testdata/12x12.png");
+    let mut decoder = png::Decoder::new(&img[..]);
+    // Need to set this so the index values don't get converted to RGBA.
+    decoder.set_transformations(png::Transformations::IDENTITY);
+    let mut reader = decoder.read_info().unwrap();
+    let mut imgdata = vec![0u8; reader.output_buffer_size()];
+    reader.next_frame(&mut imgdata[..]).unwrap();
+    SpriteTexture::new_from_pixels(
+        &imgdata[..],
+        reader.info().size().0 as usize,
+        reader.info().size().1 as usize,
+        reader.info().size().0 as usize / 16,
+        reader.info().size().1 as usize / 16,
+        256,
+    )
+    .unwrap()
+}
+
 /// There's a lot of boilerplate in setting up the offscreen renderer and extracting the rendered
 /// image, so we use a separate support fixture to manage that.
 struct RenderTestFixture {
@@ -33,24 +54,7 @@ struct RenderTestFixture {
 
 impl RenderTestFixture {
     fn new(width: u32, height: u32) -> RenderTestFixture {
-        // Load the test sprite texture.
-        // TODO: get rid of this once the sprite-loading code is done.
-        let img = include_bytes!("testdata/12x12.png");
-        let mut decoder = png::Decoder::new(&img[..]);
-        // Need to set this so the index values don't get converted to RGBA.
-        decoder.set_transformations(png::Transformations::IDENTITY);
-        let mut reader = decoder.read_info().unwrap();
-        let mut imgdata = vec![0u8; reader.output_buffer_size()];
-        reader.next_frame(&mut imgdata[..]).unwrap();
-        let tex = SpriteTexture::new_from_pixels(
-            &imgdata[..],
-            reader.info().size().0 as usize,
-            reader.info().size().1 as usize,
-            reader.info().size().0 as usize / 16,
-            reader.info().size().1 as usize / 16,
-            256,
-        )
-        .unwrap();
+        let tex = load_test_sprite_texture();
 
         let sprite_width = tex.sprite_width();
         let sprite_height = tex.sprite_height();
@@ -62,6 +66,7 @@ impl RenderTestFixture {
             [0, 255, 0].into(),
             wgpu::FilterMode::Nearest,
             wgpu::PresentMode::Fifo,
+            false,
         )
         .unwrap();
 
@@ -282,18 +287,16 @@ fn full_palette() {
             [0, 255, 0].into(),
             wgpu::FilterMode::Nearest,
             wgpu::PresentMode::Fifo,
+            false,
         )
         .unwrap();
 
         renderer.update(
-            vec![
-                SpriteCell {
-                    palette: Default::default(),
-                    sprite: 0,
-                    ..Default::default()
-                };
-                1
-            ]
+            [SpriteCell {
+                palette: Default::default(),
+                sprite: 0,
+                ..Default::default()
+            }]
             .iter(),
         );
 
@@ -310,3 +313,1077 @@ fn full_palette() {
             .into_raw();
     assert_eq!(&actual_image[..], &expected_image[..]);
 }
+
+#[test]
+fn transparent_layer_shows_base() {
+    let mut fixture = RenderTestFixture::new(1, 1);
+    fixture.renderer.set_layer_count(2);
+    assert_eq!(fixture.renderer.layer_count(), 2);
+
+    fixture.renderer.update_layer(
+        0,
+        [SpriteCell {
+            palette: Palette::mono([255, 255, 255]).set(0, [0, 0, 0]),
+            sprite: 1,
+            ..Default::default()
+        }]
+        .iter(),
+    );
+    fixture.renderer.update_layer(
+        1,
+        [SpriteCell {
+            palette: Palette::mono([255, 0, 0]),
+            sprite: 2,
+            transparent: true,
+            ..Default::default()
+        }]
+        .iter(),
+    );
+
+    // Render the frame.
+    fixture.renderer.render_frame().unwrap();
+
+    let actual_image = fixture.extract_render_result();
+    let expected_image = image::load_from_memory(include_bytes!("testdata/one_cell.png"))
+        .unwrap()
+        .to_rgba8()
+        .into_raw();
+    assert_eq!(&actual_image[..], &expected_image[..]);
+}
+
+#[test]
+fn opaque_layer_covers_base() {
+    let mut fixture = RenderTestFixture::new(1, 1);
+    fixture.renderer.set_layer_count(2);
+
+    fixture.renderer.update_layer(
+        0,
+        [SpriteCell {
+            palette: Palette::mono([255, 0, 0]),
+            sprite: 2,
+            ..Default::default()
+        }]
+        .iter(),
+    );
+    fixture.renderer.update_layer(
+        1,
+        [SpriteCell {
+            palette: Palette::mono([255, 255, 255]).set(0, [0, 0, 0]),
+            sprite: 1,
+            ..Default::default()
+        }]
+        .iter(),
+    );
+
+    // Render the frame.
+    fixture.renderer.render_frame().unwrap();
+
+    let actual_image = fixture.extract_render_result();
+    let expected_image = image::load_from_memory(include_bytes!("testdata/one_cell.png"))
+        .unwrap()
+        .to_rgba8()
+        .into_raw();
+    assert_eq!(&actual_image[..], &expected_image[..]);
+}
+
+#[test]
+fn keyed_palette_index_shows_base() {
+    let mut fixture = RenderTestFixture::new(1, 1);
+    fixture.renderer.set_layer_count(2);
+
+    // Solid black base layer.
+    fixture.renderer.update_layer(
+        0,
+        [SpriteCell {
+            palette: Palette::mono([0, 0, 0]),
+            sprite: 0,
+            ..Default::default()
+        }]
+        .iter(),
+    );
+    // The background (index 0) would be red if it weren't keyed out.
+    fixture.renderer.update_layer(
+        1,
+        [SpriteCell {
+            palette: Palette::mono([255, 255, 255]).set(0, [255, 0, 0]),
+            sprite: 1,
+            ..Default::default()
+        }
+        .with_transparent_index(0)]
+        .iter(),
+    );
+
+    // Render the frame.
+    fixture.renderer.render_frame().unwrap();
+
+    let actual_image = fixture.extract_render_result();
+    let expected_image = image::load_from_memory(include_bytes!("testdata/one_cell.png"))
+        .unwrap()
+        .to_rgba8()
+        .into_raw();
+    assert_eq!(&actual_image[..], &expected_image[..]);
+}
+
+#[test]
+fn headless_renderer() {
+    let tex = load_test_sprite_texture();
+    let mut renderer = HeadlessRenderer::new(2, 2, &tex, AdapterSelection::Default).unwrap();
+    assert_eq!(
+        renderer.output_size(),
+        (
+            2 * tex.sprite_width() as u32,
+            2 * tex.sprite_height() as u32
+        )
+    );
+
+    renderer.update(
+        [
+            SpriteCell {
+                palette: Palette::mono([255, 0, 255]).set(0, [0, 0, 0]),
+                sprite: 72,
+                ..Default::default()
+            },
+            SpriteCell {
+                palette: Palette::mono([0, 255, 255]).set(0, [0, 0, 0]),
+                sprite: 105,
+                ..Default::default()
+            },
+            SpriteCell {
+                palette: Palette::mono([255, 255, 0]).set(0, [0, 0, 0]),
+                sprite: 33,
+                ..Default::default()
+            },
+            SpriteCell {
+                palette: Palette::mono([0, 255, 0]).set(0, [0, 0, 0]),
+                sprite: 19,
+                ..Default::default()
+            },
+        ]
+        .iter(),
+    );
+
+    let actual_image = renderer.render_frame().unwrap();
+
+    let expected_image = image::load_from_memory(include_bytes!("testdata/hi.png"))
+        .unwrap()
+        .to_rgba8()
+        .into_raw();
+    assert_eq!(&actual_image[..], &expected_image[..]);
+}
+
+// The software renderer must produce pixel-identical output to the wgpu renderer, so it is checked
+// against the same golden images.
+
+fn load_golden(png_data: &[u8]) -> Vec<u8> {
+    image::load_from_memory(png_data)
+        .unwrap()
+        .to_rgba8()
+        .into_raw()
+}
+
+#[test]
+fn software_one_cell() {
+    let tex = load_test_sprite_texture();
+    let mut renderer = SoftwareRenderer::new(1, 1, &tex);
+
+    renderer.update(
+        [SpriteCell {
+            palette: Palette::mono([255, 255, 255]).set(0, [0, 0, 0]),
+            sprite: 1,
+            ..Default::default()
+        }]
+        .iter(),
+    );
+
+    let actual_image = renderer.render_frame();
+    let expected_image = load_golden(include_bytes!("testdata/one_cell.png"));
+    assert_eq!(&actual_image[..], &expected_image[..]);
+}
+
+#[test]
+fn software_2x2_with_color() {
+    let tex = load_test_sprite_texture();
+    let mut renderer = SoftwareRenderer::new(2, 2, &tex);
+
+    renderer.update(
+        [
+            SpriteCell {
+                palette: Palette::mono([255, 0, 255]).set(0, [0, 0, 0]),
+                sprite: 72,
+                ..Default::default()
+            },
+            SpriteCell {
+                palette: Palette::mono([0, 255, 255]).set(0, [0, 0, 0]),
+                sprite: 105,
+                ..Default::default()
+            },
+            SpriteCell {
+                palette: Palette::mono([255, 255, 0]).set(0, [0, 0, 0]),
+                sprite: 33,
+                ..Default::default()
+            },
+            SpriteCell {
+                palette: Palette::mono([0, 255, 0]).set(0, [0, 0, 0]),
+                sprite: 19,
+                ..Default::default()
+            },
+        ]
+        .iter(),
+    );
+
+    let actual_image = renderer.render_frame();
+    let expected_image = load_golden(include_bytes!("testdata/hi.png"));
+    assert_eq!(renderer.output_size(), (24, 24));
+    assert_eq!(&actual_image[..], &expected_image[..]);
+}
+
+#[test]
+fn software_gray() {
+    let tex = load_test_sprite_texture();
+    let mut renderer = SoftwareRenderer::new(1, 1, &tex);
+
+    renderer.update(
+        [SpriteCell {
+            palette: Palette::mono([128, 128, 128]),
+            sprite: 0,
+            ..Default::default()
+        }]
+        .iter(),
+    );
+
+    let actual_image = renderer.render_frame();
+    let expected_image = load_golden(include_bytes!("testdata/50pct_gray.png"));
+    assert_eq!(&actual_image[..], &expected_image[..]);
+}
+
+#[test]
+fn software_big() {
+    let tex = load_test_sprite_texture();
+    let mut renderer = SoftwareRenderer::new(680, 10, &tex);
+
+    renderer.update(
+        vec![
+            SpriteCell {
+                palette: Palette::mono([128, 128, 128]).set(1, [255, 0, 0]),
+                sprite: 1,
+                ..Default::default()
+            };
+            6800
+        ]
+        .iter(),
+    );
+
+    let actual_image = renderer.render_frame();
+    let expected_image = load_golden(include_bytes!("testdata/big.png"));
+    assert_eq!(&actual_image[..], &expected_image[..]);
+}
+
+#[test]
+fn software_full_palette() {
+    let img = include_bytes!("testdata/full_palette.png");
+    let mut decoder = png::Decoder::new(&img[..]);
+    // Need to set this so the index values don't get converted to RGBA.
+    decoder.set_transformations(png::Transformations::IDENTITY);
+    let mut reader = decoder.read_info().unwrap();
+    let mut imgdata = vec![0u8; reader.output_buffer_size()];
+    reader.next_frame(&mut imgdata[..]).unwrap();
+    let tex = SpriteTexture::new_from_pixels(
+        &imgdata[..],
+        reader.info().size().0 as usize,
+        reader.info().size().1 as usize,
+        reader.info().size().0 as usize,
+        reader.info().size().1 as usize,
+        1,
+    )
+    .unwrap();
+
+    let mut renderer = SoftwareRenderer::new(1, 1, &tex);
+    renderer.update([SpriteCell::default()].iter());
+
+    let actual_image = renderer.render_frame();
+    let expected_image = load_golden(include_bytes!("testdata/full_palette_output.png"));
+    assert_eq!(&actual_image[..], &expected_image[..]);
+}
+
+#[test]
+fn software_layers() {
+    let tex = load_test_sprite_texture();
+    let mut renderer = SoftwareRenderer::new(1, 1, &tex);
+    renderer.set_layer_count(3);
+    assert_eq!(renderer.layer_count(), 3);
+
+    // Solid black base layer.
+    renderer.update_layer(
+        0,
+        [SpriteCell {
+            palette: Palette::mono([0, 0, 0]),
+            sprite: 0,
+            ..Default::default()
+        }]
+        .iter(),
+    );
+    // The background (index 0) would be red if it weren't keyed out.
+    renderer.update_layer(
+        1,
+        [SpriteCell {
+            palette: Palette::mono([255, 255, 255]).set(0, [255, 0, 0]),
+            sprite: 1,
+            ..Default::default()
+        }
+        .with_transparent_index(0)]
+        .iter(),
+    );
+    // Fully transparent top layer.
+    renderer.update_layer(
+        2,
+        [SpriteCell {
+            palette: Palette::mono([0, 0, 255]),
+            sprite: 2,
+            transparent: true,
+            ..Default::default()
+        }]
+        .iter(),
+    );
+
+    let actual_image = renderer.render_frame();
+    let expected_image = load_golden(include_bytes!("testdata/one_cell.png"));
+    assert_eq!(&actual_image[..], &expected_image[..]);
+}
+
+#[test]
+fn headless_resize_grid() {
+    let tex = load_test_sprite_texture();
+    let mut renderer = HeadlessRenderer::new(2, 2, &tex, AdapterSelection::Default).unwrap();
+    renderer.resize_grid(3, 1);
+    assert_eq!(renderer.dimensions(), (3, 1));
+
+    let (width, height) = (3 * tex.sprite_width() as u32, tex.sprite_height() as u32);
+    assert_eq!(renderer.output_size(), (width, height));
+    let frame = renderer.render_frame().unwrap();
+    assert_eq!(frame.len(), (width * height * 4) as usize);
+}
+
+#[test]
+fn software_resize_grid() {
+    let tex = load_test_sprite_texture();
+    let mut renderer = SoftwareRenderer::new(2, 2, &tex);
+    renderer.set_layer_count(2);
+    renderer.resize_grid(3, 1);
+    assert_eq!(renderer.dimensions(), (3, 1));
+    assert_eq!(renderer.layer_count(), 2);
+
+    let (width, height) = (3 * tex.sprite_width() as u32, tex.sprite_height() as u32);
+    assert_eq!(renderer.output_size(), (width, height));
+    assert_eq!(renderer.render_frame().len(), (width * height * 4) as usize);
+}
+
+#[test]
+fn viewport_scaling_modes() {
+    let viewport = |mode| Viewport::new((100, 50), (30, 20), mode);
+    assert_eq!(
+        viewport(ScalingMode::Fit),
+        Viewport {
+            x: 12,
+            y: 0,
+            width: 75,
+            height: 50
+        }
+    );
+    assert_eq!(
+        viewport(ScalingMode::IntegerFit),
+        Viewport {
+            x: 20,
+            y: 5,
+            width: 60,
+            height: 40
+        }
+    );
+    assert_eq!(
+        viewport(ScalingMode::Stretch),
+        Viewport {
+            x: 0,
+            y: 0,
+            width: 100,
+            height: 50
+        }
+    );
+    assert_eq!(
+        viewport(ScalingMode::Fill),
+        Viewport {
+            x: 0,
+            y: -9,
+            width: 100,
+            height: 67
+        }
+    );
+    // Integer scaling can't shrink the image, so it falls back to fitting.
+    assert_eq!(
+        Viewport::new((15, 10), (30, 20), ScalingMode::IntegerFit),
+        Viewport::new((15, 10), (30, 20), ScalingMode::Fit)
+    );
+}
+
+#[test]
+fn viewport_visible_part() {
+    let viewport = Viewport::new((100, 50), (30, 20), ScalingMode::Fill);
+    assert_eq!(
+        viewport.visible_part((100, 50)),
+        Some(Viewport {
+            x: 0,
+            y: 0,
+            width: 100,
+            height: 50
+        })
+    );
+    let viewport = Viewport::new((100, 50), (30, 20), ScalingMode::IntegerFit);
+    assert_eq!(viewport.visible_part((100, 50)), Some(viewport));
+    assert_eq!(
+        Viewport::new((0, 0), (30, 20), ScalingMode::Fit).visible_part((0, 0)),
+        None
+    );
+}
+
+#[test]
+fn viewport_cell_at() {
+    let viewport = Viewport::new((100, 50), (30, 20), ScalingMode::IntegerFit);
+    // The viewport is at (20, 5) and each of the 3x2 cells is 20 pixels square.
+    assert_eq!(viewport.cell_at((20.0, 5.0), (3, 2)), Some((0, 0)));
+    assert_eq!(viewport.cell_at((79.9, 44.9), (3, 2)), Some((2, 1)));
+    assert_eq!(viewport.cell_at((45.0, 30.0), (3, 2)), Some((1, 1)));
+    assert_eq!(viewport.cell_at((19.9, 5.0), (3, 2)), None);
+    assert_eq!(viewport.cell_at((80.0, 5.0), (3, 2)), None);
+    assert_eq!(viewport.cell_at((50.0, 45.0), (3, 2)), None);
+}
+
+#[test]
+fn dirty_row_ranges() {
+    assert_eq!(dirty_ranges(&[]), vec![]);
+    assert_eq!(dirty_ranges(&[false, false]), vec![]);
+    assert_eq!(
+        dirty_ranges(&[true, true, false, true, false, false, true, true, true]),
+        vec![0..2, 3..4, 6..9]
+    );
+}
+
+#[test]
+fn partial_layer_upload() {
+    let (width, height) = (3, 4);
+    // A different sprite and palette in every cell of the top layer, keying out the background
+    // so the base layer shows through.
+    let base = vec![
+        SpriteCell {
+            palette: Palette::mono([0, 0, 255]),
+            sprite: 3,
+            ..Default::default()
+        };
+        width * height
+    ];
+    let mut top = (0..width * height)
+        .map(|i| {
+            SpriteCell {
+                palette: Palette::mono([0, 0, 0]).set(1, [i as u8 * 20, 255, 0]),
+                sprite: i as u32 + 1,
+                ..Default::default()
+            }
+            .with_transparent_index(0)
+        })
+        .collect::<Vec<_>>();
+    let render = |fixture: &mut RenderTestFixture, top: &[SpriteCell]| {
+        fixture.renderer.update_layer(0, base.iter());
+        fixture.renderer.update_layer(1, top.iter());
+        fixture.renderer.render_frame().unwrap();
+        fixture.extract_render_result()
+    };
+
+    let mut fixture = RenderTestFixture::new(width as u32, height as u32);
+    fixture.renderer.set_layer_count(2);
+    let first = render(&mut fixture, &top);
+    // Change one cell in a middle row, so only that row is uploaded.
+    top[2 * width + 1] = SpriteCell {
+        palette: Palette::mono([255, 0, 255]),
+        sprite: 7,
+        ..Default::default()
+    };
+    let second = render(&mut fixture, &top);
+    assert_ne!(first, second);
+
+    let mut fresh = RenderTestFixture::new(width as u32, height as u32);
+    fresh.renderer.set_layer_count(2);
+    assert_eq!(second, render(&mut fresh, &top));
+}
+
+#[test]
+fn headless_frame_stats() {
+    let tex = load_test_sprite_texture();
+    let mut renderer = HeadlessRenderer::new(40, 3, &tex, AdapterSelection::Default).unwrap();
+    let yellow = [255, 255, 85, 255];
+    // Whether the overlay's yellow text shows in the top left cell of `frame`, and not in the
+    // last cell of the top row, past the end of the text.
+    let overlay_shown = |frame: &[u8]| {
+        let (cell_w, cell_h) = (tex.sprite_width(), tex.sprite_height());
+        let row_len = 40 * cell_w;
+        let cell_has_yellow = |cx: usize| {
+            (0..cell_h).any(|y| {
+                let row = &frame[(y * row_len + cx * cell_w) * 4..][..cell_w * 4];
+                row.chunks(4).any(|p| p == yellow)
+            })
+        };
+        cell_has_yellow(0) && !cell_has_yellow(39)
+    };
+    renderer.set_stats_overlay(true);
+    renderer.set_gpu_timing(true);
+    for _ in 0..3 {
+        assert!(overlay_shown(&renderer.render_frame().unwrap()));
+    }
+    let stats = renderer.frame_stats().unwrap();
+    assert_eq!(stats.frames, 3);
+    assert_eq!(stats.frame_time.len(), 3);
+    assert_eq!(stats.render_time.len(), 3);
+    // The overlay doesn't count as a layer.
+    assert_eq!(renderer.layer_count(), 1);
+
+    // The overlay stays put when the camera pans away from the top left.
+    renderer.set_camera(
+        Camera {
+            zoom: 4.0,
+            center: (35.0, 2.5),
+        },
+        std::time::Duration::ZERO,
+    );
+    assert!(overlay_shown(&renderer.render_frame().unwrap()));
+
+    renderer.set_stats_overlay(false);
+    assert!(!overlay_shown(&renderer.render_frame().unwrap()));
+}
+
+#[test]
+fn camera_clamped() {
+    let grid = (40, 20);
+    assert_eq!(
+        Camera {
+            zoom: 0.5,
+            center: (0.0, 0.0)
+        }
+        .clamped(grid),
+        Camera::full_view(grid)
+    );
+    // At 2x zoom a quarter of the grid is visible, so the center can't get within 10x5 cells of
+    // the edges.
+    assert_eq!(
+        Camera {
+            zoom: 2.0,
+            center: (1.0, 19.0)
+        }
+        .clamped(grid),
+        Camera {
+            zoom: 2.0,
+            center: (10.0, 15.0)
+        }
+    );
+}
+
+#[test]
+fn camera_uv_transform() {
+    let grid = (40, 20);
+    assert_eq!(
+        Camera::full_view(grid).uv_transform(grid),
+        ([1.0, 1.0], [0.0, 0.0])
+    );
+    let camera = Camera {
+        zoom: 4.0,
+        center: (30.0, 5.0),
+    };
+    assert_eq!(camera.uv_transform(grid), ([0.25, 0.25], [0.625, 0.125]));
+}
+
+#[test]
+fn camera_transition() {
+    let from = Camera::full_view((40, 20));
+    let to = Camera {
+        zoom: 3.0,
+        center: (10.0, 10.0),
+    };
+    let transition = CameraTransition {
+        from,
+        to,
+        start: time::Duration::seconds(1),
+        length: time::Duration::seconds(2),
+    };
+    assert_eq!(transition.camera_at(time::Duration::ZERO), from);
+    assert_eq!(
+        transition.camera_at(time::Duration::seconds(2)),
+        from.lerp(to, 0.5)
+    );
+    assert_eq!(transition.camera_at(time::Duration::seconds(3)), to);
+    assert_eq!(
+        CameraTransition::fixed(to).camera_at(time::Duration::ZERO),
+        to
+    );
+}
+
+#[test]
+fn post_process_chain() {
+    let mut fixture = RenderTestFixture::new(1, 1);
+    let fill = fill_pass([1.0, 0.0, 0.0, 1.0]);
+    let invert = PostProcessPass::new(
+        "@fragment
+         fn fs_main(in: ScreenVertexOutput) -> @location(0) vec4<f32> {
+           let c = textureSample(screen_texture, screen_sampler, in.uv);
+           return vec4<f32>(1.0 - c.rgb, c.a);
+         }",
+    );
+    fixture.renderer.add_post_process(&fill).unwrap();
+    fixture.renderer.add_post_process(&invert).unwrap();
+
+    fixture.renderer.render_frame().unwrap();
+    let image = fixture.extract_render_result();
+    assert!(image.chunks(4).all(|p| p == [0, 255, 255, 255]));
+
+    fixture
+        .renderer
+        .set_post_process_uniforms(0, [0.0f32, 0.0, 1.0, 1.0]);
+    fixture.renderer.render_frame().unwrap();
+    let image = fixture.extract_render_result();
+    assert!(image.chunks(4).all(|p| p == [255, 255, 0, 255]));
+}
+
+#[test]
+fn invalid_shaders() {
+    let mut fixture = RenderTestFixture::new(1, 1);
+    assert!(matches!(
+        fixture.renderer.set_screen_shader("fn fs_main("),
+        Err(RenderError::ShaderError(_))
+    ));
+    // Compiles, but has the wrong entry point.
+    assert!(matches!(
+        fixture.renderer.add_post_process(&PostProcessPass::new(
+            "@fragment
+             fn main(in: ScreenVertexOutput) -> @location(0) vec4<f32> {
+               return vec4<f32>(1.0);
+             }"
+        )),
+        Err(RenderError::ShaderError(_))
+    ));
+    // The renderer still works with its previous shaders.
+    fixture.renderer.render_frame().unwrap();
+}
+
+/// A post-processing pass that fills the screen with the color in its uniforms.
+fn fill_pass(color: [f32; 4]) -> PostProcessPass {
+    PostProcessPass::new(
+        "struct Fill { color: vec4<f32> }
+         @group(2) @binding(0) var<uniform> fill: Fill;
+         @fragment
+         fn fs_main(in: ScreenVertexOutput) -> @location(0) vec4<f32> {
+           return fill.color;
+         }",
+    )
+    .with_uniforms(color)
+}
+
+#[test]
+fn effects_compile() {
+    let mut fixture = RenderTestFixture::new(2, 2);
+    for effect in [
+        Effect::Scanlines { intensity: 0.5 },
+        Effect::Curvature { amount: 0.1 },
+        Effect::ChromaticAberration { offset: 2.0 },
+        Effect::Vignette { intensity: 0.5 },
+        Effect::PhosphorPersistence { decay: 0.5 },
+        Effect::ColorGrading(Default::default()),
+    ] {
+        fixture.renderer.add_post_process(&effect.into()).unwrap();
+    }
+    fixture.renderer.render_frame().unwrap();
+}
+
+#[test]
+fn effects_toggle() {
+    let mut fixture = RenderTestFixture::new(1, 1);
+    fixture.renderer.render_frame().unwrap();
+    let plain = fixture.extract_render_result();
+
+    fixture
+        .renderer
+        .add_post_process(&fill_pass([1.0, 0.0, 0.0, 1.0]))
+        .unwrap();
+    fixture
+        .renderer
+        .add_post_process(&Effect::ColorGrading(Default::default()).into())
+        .unwrap();
+    fixture.renderer.render_frame().unwrap();
+    let image = fixture.extract_render_result();
+    assert!(image.chunks(4).all(|p| p == [255, 0, 0, 255]));
+
+    // With the fill pass off, identity color grading leaves the image unchanged.
+    fixture.renderer.set_post_process_enabled(0, false);
+    fixture.renderer.render_frame().unwrap();
+    assert_eq!(fixture.extract_render_result(), plain);
+
+    fixture.renderer.set_post_process_enabled(1, false);
+    fixture.renderer.render_frame().unwrap();
+    assert_eq!(fixture.extract_render_result(), plain);
+}
+
+#[test]
+fn phosphor_persistence() {
+    let mut fixture = RenderTestFixture::new(1, 1);
+    fixture
+        .renderer
+        .add_post_process(&fill_pass([1.0, 1.0, 1.0, 1.0]))
+        .unwrap();
+    fixture
+        .renderer
+        .add_post_process(&Effect::PhosphorPersistence { decay: 0.5 }.into())
+        .unwrap();
+    fixture.renderer.render_frame().unwrap();
+    let image = fixture.extract_render_result();
+    assert!(image.chunks(4).all(|p| p == [255, 255, 255, 255]));
+
+    // The fill turns black, but the previous frame lingers at half brightness.
+    fixture
+        .renderer
+        .set_post_process_uniforms(0, [0.0f32, 0.0, 0.0, 1.0]);
+    fixture.renderer.render_frame().unwrap();
+    let image = fixture.extract_render_result();
+    assert!(image
+        .chunks(4)
+        .all(|p| (127..=128).contains(&p[0]) && p[0] == p[1] && p[1] == p[2]));
+}
+
+#[test]
+fn effect_uniforms() {
+    assert_eq!(
+        Effect::Scanlines { intensity: 0.25 }.uniforms(),
+        [0.25, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]
+    );
+    assert_eq!(
+        Effect::ColorGrading(ColorGrading {
+            tint: [0.2, 1.0, 0.3],
+            ..Default::default()
+        })
+        .uniforms(),
+        [1.0, 1.0, 1.0, 1.0, 0.2, 1.0, 0.3, 0.0]
+    );
+    assert!(PostProcessPass::from(Effect::PhosphorPersistence { decay: 0.9 }).uses_history);
+    assert!(!PostProcessPass::from(Effect::Vignette { intensity: 0.9 }).uses_history);
+}
+
+#[test]
+fn custom_cell_shader() {
+    let mut fixture = RenderTestFixture::new(2, 1);
+    fixture
+        .renderer
+        .set_cell_shader(
+            "fn shade_cell(cell: Cell) -> vec4<f32> {
+               // Keep the palette lookup and sprite reads so they are compiled too.
+               let base = palette_color(cell, sprite_pixel(cell.sprite, vec2<u32>(0u, 0u)));
+               let t = fract(cell_globals.elapsed_time) * 0.0;
+               return vec4<f32>(f32(cell.user_data) / 255.0, f32(cell.coords.x), t, base.a);
+             }",
+        )
+        .unwrap();
+    fixture.renderer.update(
+        [
+            SpriteCell {
+                user_data: 51,
+                ..Default::default()
+            },
+            SpriteCell {
+                user_data: 255,
+                ..Default::default()
+            },
+        ]
+        .iter(),
+    );
+    fixture.renderer.render_frame().unwrap();
+    let image = fixture.extract_render_result();
+    let width = 2 * fixture.sprite_width as usize;
+    for (i, pixel) in image.chunks(4).enumerate() {
+        if i % width < fixture.sprite_width as usize {
+            assert_eq!(pixel, [51, 0, 0, 255]);
+        } else {
+            assert_eq!(pixel, [255, 255, 0, 255]);
+        }
+    }
+}
+
+#[test]
+fn animated_cells() {
+    use crate::graphics::animation::{Animation, AnimationMode};
+    use std::time::Duration;
+
+    let hour = Duration::from_secs(3600);
+    let mut fixture = RenderTestFixture::new(3, 1);
+    fixture
+        .renderer
+        .set_cell_shader(
+            "fn shade_cell(cell: Cell) -> vec4<f32> {
+               return vec4<f32>(f32(cell.sprite) / 255.0, 0.0, 0.0, 1.0);
+             }",
+        )
+        .unwrap();
+    // The zero-length first frame is skipped straight away.
+    let once = fixture.renderer.add_animation(
+        &Animation::new(AnimationMode::Once)
+            .with_frame(4, Duration::ZERO)
+            .with_frame(9, hour)
+            .with_frame(11, hour),
+    );
+    let looping = fixture
+        .renderer
+        .add_animation(&Animation::new(AnimationMode::Loop).with_frame(20, hour));
+    fixture.renderer.update(
+        [
+            SpriteCell {
+                sprite: 3,
+                ..Default::default()
+            },
+            SpriteCell {
+                sprite: 3,
+                animation: Some(once),
+                ..Default::default()
+            },
+            SpriteCell {
+                sprite: 3,
+                animation: Some(looping),
+                ..Default::default()
+            },
+        ]
+        .iter(),
+    );
+    fixture.renderer.render_frame().unwrap();
+    let image = fixture.extract_render_result();
+    let width = 3 * fixture.sprite_width as usize;
+    for (i, pixel) in image.chunks(4).enumerate() {
+        let expected = [3, 9, 20][(i % width) / fixture.sprite_width as usize];
+        assert_eq!(pixel, [expected, 0, 0, 255]);
+    }
+}
+
+#[test]
+fn palette_cycled_cells() {
+    use crate::graphics::animation::PaletteCycle;
+    use crate::resources::color::Color;
+    use std::time::Duration;
+
+    let hour = Duration::from_secs(3600);
+    let mut fixture = RenderTestFixture::new(3, 1);
+    fixture
+        .renderer
+        .set_cell_shader(
+            "fn shade_cell(cell: Cell) -> vec4<f32> {
+               return palette_color(cell, 1u);
+             }",
+        )
+        .unwrap();
+    // Within the first period a blinking cell shows its own palette, and a gradient is at its
+    // first color.
+    let blink = fixture.renderer.add_palette_cycle(&PaletteCycle::Blink {
+        palette: Palette::mono([255, 255, 255]),
+        period: hour,
+    });
+    let gradient = fixture.renderer.add_palette_cycle(&PaletteCycle::Gradient {
+        index: 1,
+        colors: vec![Color::new(10, 20, 30), Color::new(255, 255, 255)],
+        period: hour,
+    });
+    fixture.renderer.update(
+        [
+            SpriteCell::default(),
+            SpriteCell {
+                palette_cycle: Some(blink),
+                ..Default::default()
+            },
+            SpriteCell {
+                palette_cycle: Some(gradient),
+                ..Default::default()
+            },
+        ]
+        .iter(),
+    );
+    fixture.renderer.render_frame().unwrap();
+    let image = fixture.extract_render_result();
+    let width = 3 * fixture.sprite_width as usize;
+    for (i, pixel) in image.chunks(4).enumerate() {
+        let expected = [[0, 0, 0xaa, 255], [0, 0, 0xaa, 255], [10, 20, 30, 255]]
+            [(i % width) / fixture.sprite_width as usize];
+        assert_eq!(pixel, expected);
+    }
+}
+
+#[test]
+fn invalid_cell_shader() {
+    let mut fixture = RenderTestFixture::new(1, 1);
+    assert!(matches!(
+        fixture
+            .renderer
+            .set_cell_shader("fn shade_cell(cell: Cell) -> f32 { return 1.0; }"),
+        Err(RenderError::ShaderError(_))
+    ));
+    fixture.renderer.render_frame().unwrap();
+}
+
+/// Two 2x2 sprites: sprite 0 uses palette index 1 everywhere, and sprite 1 index 2.
+fn small_sprite_texture() -> SpriteTexture {
+    SpriteTexture::new_from_pixels(&[1, 1, 2, 2, 1, 1, 2, 2], 4, 2, 2, 2, 2).unwrap()
+}
+
+#[test]
+fn headless_set_sprite_texture() {
+    let tex = load_test_sprite_texture();
+    let mut renderer = HeadlessRenderer::new(3, 2, &tex, AdapterSelection::Default).unwrap();
+    let cell = SpriteCell {
+        palette: Palette::mono([0, 0, 0]).set(2, [255, 0, 0]),
+        sprite: 1,
+        ..Default::default()
+    };
+    renderer.update([cell; 6].iter());
+    renderer.set_sprite_texture(&small_sprite_texture());
+    assert_eq!(renderer.dimensions(), (3, 2));
+    assert_eq!(renderer.output_size(), (6, 4));
+
+    let frame = renderer.render_frame().unwrap();
+    assert_eq!(frame.len(), 6 * 4 * 4);
+    assert!(frame.chunks(4).all(|p| p == [255, 0, 0, 255]));
+}
+
+#[test]
+fn software_set_sprite_texture() {
+    let tex = load_test_sprite_texture();
+    let mut renderer = SoftwareRenderer::new(3, 2, &tex);
+    let cell = SpriteCell {
+        palette: Palette::mono([0, 0, 0]).set(2, [255, 0, 0]),
+        sprite: 1,
+        ..Default::default()
+    };
+    renderer.update([cell; 6].iter());
+    renderer.set_sprite_texture(&small_sprite_texture());
+    assert_eq!(renderer.dimensions(), (3, 2));
+    assert_eq!(renderer.output_size(), (6, 4));
+
+    let frame = renderer.render_frame();
+    assert_eq!(frame.len(), 6 * 4 * 4);
+    assert!(frame.chunks(4).all(|p| p == [255, 0, 0, 255]));
+}
+
+#[test]
+fn software_animated_cells() {
+    use crate::graphics::animation::{Animation, AnimationMode};
+    use std::time::Duration;
+
+    let frame_time = Duration::from_millis(100);
+    let mut renderer =
+        SoftwareRenderer::new(2, 1, &small_sprite_texture()).with_fixed_frame_time(frame_time);
+    let animation = renderer.add_animation(
+        &Animation::new(AnimationMode::Loop)
+            .with_frame(0, frame_time)
+            .with_frame(1, frame_time),
+    );
+    let palette = Palette::mono([0, 0, 0])
+        .set(1, [255, 0, 0])
+        .set(2, [0, 255, 0]);
+    let still = SpriteCell {
+        palette,
+        sprite: 1,
+        ..Default::default()
+    };
+    let animated = SpriteCell {
+        animation: Some(animation),
+        ..still
+    };
+    // Left and right cell colors, as seen on the top row of the output.
+    fn colors(frame: &[u8]) -> [&[u8]; 2] {
+        [&frame[0..4], &frame[8..12]]
+    }
+    const RED: &[u8] = &[255, 0, 0, 255];
+    const GREEN: &[u8] = &[0, 255, 0, 255];
+
+    renderer.update([animated, still].iter());
+    assert_eq!(colors(&renderer.render_frame()), [RED, GREEN]);
+    assert_eq!(colors(&renderer.render_frame()), [GREEN, GREEN]);
+    // Setting the animation later starts it from its first frame.
+    renderer.update([animated, animated].iter());
+    assert_eq!(colors(&renderer.render_frame()), [RED, RED]);
+    assert_eq!(colors(&renderer.render_frame()), [GREEN, GREEN]);
+}
+
+/// Palette cycles of each kind, all acting on palette index 1, with a step or period of a second.
+fn test_palette_cycles(renderer: &mut impl RenderInterface) -> [SpriteCell; 3] {
+    use crate::graphics::animation::PaletteCycle;
+    use crate::resources::color::Color;
+    use std::time::Duration;
+
+    let second = Duration::from_secs(1);
+    let rotate = renderer.add_palette_cycle(&PaletteCycle::Rotate {
+        first: 1,
+        count: 2,
+        step: second,
+    });
+    let blink = renderer.add_palette_cycle(&PaletteCycle::Blink {
+        palette: Palette::mono([0, 0, 0]).set(1, [0, 0, 255]),
+        period: second,
+    });
+    let gradient = renderer.add_palette_cycle(&PaletteCycle::Gradient {
+        index: 1,
+        colors: vec![Color::new(0, 0, 0), Color::new(200, 100, 0)],
+        period: 4 * second,
+    });
+    let cell = SpriteCell {
+        palette: Palette::mono([0, 0, 0])
+            .set(1, [255, 0, 0])
+            .set(2, [0, 255, 0]),
+        sprite: 0,
+        ..Default::default()
+    };
+    [rotate, blink, gradient].map(|id| SpriteCell {
+        palette_cycle: Some(id),
+        ..cell
+    })
+}
+
+// After a second the rotation and blink have taken a step, and the gradient is halfway to its
+// second color.
+const PALETTE_CYCLE_STEPS: [[[u8; 4]; 3]; 2] = [
+    [[255, 0, 0, 255], [255, 0, 0, 255], [0, 0, 0, 255]],
+    [[0, 255, 0, 255], [0, 0, 255, 255], [100, 50, 0, 255]],
+];
+
+#[test]
+fn palette_cycle_steps() {
+    let mut fixture = RenderTestFixture::new(3, 1);
+    fixture
+        .renderer
+        .set_cell_shader(
+            "fn shade_cell(cell: Cell) -> vec4<f32> {
+               return palette_color(cell, 1u);
+             }",
+        )
+        .unwrap();
+    let cells = test_palette_cycles(&mut fixture.renderer);
+    fixture.renderer.update(cells.iter());
+    let width = 3 * fixture.sprite_width as usize;
+    for (t, expected) in PALETTE_CYCLE_STEPS.iter().enumerate() {
+        fixture.renderer.elapsed_time = time::Duration::seconds(t as i64);
+        fixture.renderer.render_frame().unwrap();
+        let image = fixture.extract_render_result();
+        for (i, pixel) in image.chunks(4).enumerate() {
+            assert_eq!(pixel, expected[(i % width) / fixture.sprite_width as usize]);
+        }
+    }
+}
+
+#[test]
+fn software_palette_cycles() {
+    let mut renderer = SoftwareRenderer::new(3, 1, &small_sprite_texture())
+        .with_fixed_frame_time(std::time::Duration::from_secs(1));
+    let cells = test_palette_cycles(&mut renderer);
+    renderer.update(cells.iter());
+    for expected in PALETTE_CYCLE_STEPS {
+        let frame = renderer.render_frame();
+        // The top left pixel of each 2x2 cell.
+        for (x, color) in expected.iter().enumerate() {
+            assert_eq!(&frame[x * 8..x * 8 + 4], color);
+        }
+    }
+}
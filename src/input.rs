@@ -14,7 +14,127 @@
 
 use std::collections::HashMap;
 
-pub use winit::event::{ElementState, MouseButton, VirtualKeyCode};
+macro_rules! keys {
+    ($($key:ident),* $(,)?) => {
+        /// Symbolic name of a key on the keyboard. Names follow the physical key rather than the
+        /// character it produces; `Key::A` is reported whether or not shift is held.
+        ///
+        /// Every key has a stable name (see `Key::name()`) that round-trips through `FromStr`, so
+        /// keymaps can be saved to and loaded from configuration files.
+        #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub enum Key {
+            $(
+                #[doc = concat!("The `", stringify!($key), "` key.")]
+                $key,
+            )*
+        }
+
+        impl Key {
+            /// Every key, in declaration order.
+            pub const ALL: &'static [Key] = &[$(Key::$key),*];
+
+            /// The stable name of this key, as accepted by `Key::from_str()`.
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Key::$key => stringify!($key),)*
+                }
+            }
+        }
+
+        impl std::str::FromStr for Key {
+            type Err = String;
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match s {
+                    $(stringify!($key) => Ok(Key::$key),)*
+                    _ => Err(format!("Unknown key name: {}", s)),
+                }
+            }
+        }
+
+        impl From<winit::event::VirtualKeyCode> for Key {
+            fn from(k: winit::event::VirtualKeyCode) -> Self {
+                match k {
+                    $(winit::event::VirtualKeyCode::$key => Key::$key,)*
+                }
+            }
+        }
+    };
+}
+
+keys! {
+    Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0,
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    Escape,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    F13, F14, F15, F16, F17, F18, F19, F20, F21, F22, F23, F24,
+    Snapshot, Scroll, Pause,
+    Insert, Home, Delete, End, PageDown, PageUp,
+    Left, Up, Right, Down,
+    Back, Return, Space, Compose, Caret,
+    Numlock, Numpad0, Numpad1, Numpad2, Numpad3, Numpad4,
+    Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+    NumpadAdd, NumpadDivide, NumpadDecimal, NumpadComma, NumpadEnter,
+    NumpadEquals, NumpadMultiply, NumpadSubtract,
+    AbntC1, AbntC2, Apostrophe, Apps, Asterisk, At, Ax, Backslash, Calculator, Capital,
+    Colon, Comma, Convert, Equals, Grave, Kana, Kanji,
+    LAlt, LBracket, LControl, LShift, LWin,
+    Mail, MediaSelect, MediaStop, Minus, Mute, MyComputer,
+    NavigateForward, NavigateBackward, NextTrack, NoConvert, OEM102, Period, PlayPause,
+    Plus, Power, PrevTrack,
+    RAlt, RBracket, RControl, RShift, RWin,
+    Semicolon, Slash, Sleep, Stop, Sysrq, Tab, Underline, Unlabeled,
+    VolumeDown, VolumeUp, Wake,
+    WebBack, WebFavorites, WebForward, WebHome, WebRefresh, WebSearch, WebStop,
+    Yen, Copy, Paste, Cut,
+}
+
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Whether a key or mouse button was pressed or released.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ButtonState {
+    /// The button was pressed.
+    Pressed,
+    /// The button was released.
+    Released,
+}
+
+impl From<winit::event::ElementState> for ButtonState {
+    fn from(s: winit::event::ElementState) -> Self {
+        match s {
+            winit::event::ElementState::Pressed => ButtonState::Pressed,
+            winit::event::ElementState::Released => ButtonState::Released,
+        }
+    }
+}
+
+/// A mouse button.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MouseButton {
+    /// The primary (usually left) button.
+    Left,
+    /// The secondary (usually right) button.
+    Right,
+    /// The middle button, often the scroll wheel.
+    Middle,
+    /// Any other button, identified by a backend-specific number.
+    Other(u16),
+}
+
+impl From<winit::event::MouseButton> for MouseButton {
+    fn from(b: winit::event::MouseButton) -> Self {
+        match b {
+            winit::event::MouseButton::Left => MouseButton::Left,
+            winit::event::MouseButton::Right => MouseButton::Right,
+            winit::event::MouseButton::Middle => MouseButton::Middle,
+            winit::event::MouseButton::Other(n) => MouseButton::Other(n),
+        }
+    }
+}
 
 /// State of the modifier keys.
 #[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
@@ -50,9 +170,9 @@ pub enum KeyboardEvent {
         /// The raw scancode of the key.
         scancode: u32,
         /// Whether the key was pressed or released.
-        state: ElementState,
+        state: ButtonState,
         /// If the key can be interpreted as a KeyCode, it will be populated here.
-        virtual_keycode: Option<VirtualKeyCode>,
+        virtual_keycode: Option<Key>,
         /// The modifiers active for this event.
         modifiers: ModifiersState,
     },
@@ -63,8 +183,8 @@ impl From<winit::event::KeyboardInput> for KeyboardEvent {
         #[allow(deprecated)]
         KeyboardEvent::KeyPress {
             scancode: k.scancode,
-            state: k.state,
-            virtual_keycode: k.virtual_keycode,
+            state: k.state.into(),
+            virtual_keycode: k.virtual_keycode.map(Key::from),
             modifiers: k.modifiers.into(),
         }
     }
@@ -89,16 +209,91 @@ pub enum MouseEvent {
     CursorEntered,
     /// The mouse cursor left the window.
     CursorLeft,
+    /// The cursor moved into a sprite cell, either from another cell or from outside the grid.
+    CellEntered {
+        /// The cell the cursor is now over.
+        cell: (u32, u32),
+    },
+    /// The cursor moved out of a sprite cell. This is sent before the corresponding
+    /// `CellEntered`.
+    CellLeft {
+        /// The cell the cursor was over.
+        cell: (u32, u32),
+    },
+    /// The cursor is still inside the window, but moved off the grid (e.g. into the letterbox
+    /// area around it).
+    CursorLeftGrid,
     /// A mouse button was pressed.
     Button {
         /// Pressed/released
-        state: ElementState,
+        state: ButtonState,
+        /// Which button
+        button: MouseButton,
+        /// The sprite cell under the cursor, if any.
+        cell: Option<(u32, u32)>,
+        /// Modifier key state
+        modifiers: ModifiersState,
+    },
+    /// The mouse wheel was scrolled.
+    Wheel {
+        /// How far the wheel was scrolled.
+        delta: ScrollDelta,
+        /// The sprite cell under the cursor, if any.
+        cell: Option<(u32, u32)>,
+        /// Modifier key state
+        modifiers: ModifiersState,
+    },
+    /// The cursor left the cell where a button was pressed while the button was held down.
+    DragStart {
+        /// The button being held.
+        button: MouseButton,
+        /// The cell where the button was pressed.
+        cell: (u32, u32),
+    },
+    /// The cursor moved into a new cell during a drag.
+    DragMove {
+        /// The button being held.
+        button: MouseButton,
+        /// The cell now under the cursor.
+        cell: (u32, u32),
+    },
+    /// The button was released, ending a drag.
+    DragEnd {
+        /// The button that was released.
+        button: MouseButton,
+        /// The cell under the cursor when the button was released, if any.
+        cell: Option<(u32, u32)>,
+    },
+    /// A button was pressed twice in quick succession over the same cell. This is sent after the
+    /// second `Button` event.
+    DoubleClick {
         /// Which button
         button: MouseButton,
+        /// The cell that was clicked.
+        cell: (u32, u32),
         /// Modifier key state
         modifiers: ModifiersState,
     },
-    // TODO: mouse wheel, etc.
+}
+
+/// Amount scrolled by a mouse wheel or touchpad. Positive values indicate movement forward (away
+/// from the user) or rightwards.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ScrollDelta {
+    /// Horizontal and vertical scroll amount, in lines/rows.
+    Lines(f32, f32),
+    /// Horizontal and vertical scroll amount, in pixels. Devices that support precise scrolling
+    /// (e.g. touchpads) report this instead of `Lines`.
+    Pixels(f64, f64),
+}
+
+impl From<winit::event::MouseScrollDelta> for ScrollDelta {
+    fn from(d: winit::event::MouseScrollDelta) -> Self {
+        match d {
+            winit::event::MouseScrollDelta::LineDelta(x, y) => ScrollDelta::Lines(x, y),
+            winit::event::MouseScrollDelta::PixelDelta(p) => ScrollDelta::Pixels(p.x, p.y),
+        }
+    }
 }
 
 /// Window-level events.
@@ -160,8 +355,17 @@ impl std::convert::TryFrom<winit::event::Event<'_, ()>> for Event {
                     modifiers,
                     ..
                 } => Ok(Event::Mouse(MouseEvent::Button {
-                    state: *state,
-                    button: *button,
+                    state: (*state).into(),
+                    button: (*button).into(),
+                    cell: None,
+                    modifiers: (*modifiers).into(),
+                })),
+                #[allow(deprecated)]
+                winit::event::WindowEvent::MouseWheel {
+                    delta, modifiers, ..
+                } => Ok(Event::Mouse(MouseEvent::Wheel {
+                    delta: (*delta).into(),
+                    cell: None,
                     modifiers: (*modifiers).into(),
                 })),
                 // We have to handle this one in Engine::run() directly, since it depends on a lot of
@@ -173,10 +377,145 @@ impl std::convert::TryFrom<winit::event::Event<'_, ()>> for Event {
         }
     }
 }
+
+/// Maximum time between two presses of the same button for them to count as a double click.
+const DOUBLE_CLICK_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// A button that is currently held down.
+#[derive(Copy, Clone, Debug)]
+struct HeldButton {
+    button: MouseButton,
+    /// The most recent cell the cursor was over while the button was held.
+    cell: (u32, u32),
+    dragging: bool,
+}
+
+/// Tracks the cursor between events, so that button and wheel events can be annotated with the
+/// cell under the cursor, and cell enter/leave events, drags and double clicks can be
+/// synthesized. Backends feed every event through `process()` before handing it to the driver.
+#[derive(Default, Debug)]
+pub(crate) struct MouseTracker {
+    cell: Option<(u32, u32)>,
+    held: Option<HeldButton>,
+    last_click: Option<(MouseButton, (u32, u32), std::time::Instant)>,
+}
+
+impl MouseTracker {
+    /// Process an event that occurred at time `now`, returning the events to deliver.
+    pub(crate) fn process(&mut self, event: Event, now: std::time::Instant) -> Vec<Event> {
+        let mut events = vec![];
+        match event {
+            Event::Mouse(MouseEvent::CursorMoved {
+                sprite_position, ..
+            }) => {
+                events.push(event);
+                if self.cell != Some(sprite_position) {
+                    if let Some(cell) = self.cell {
+                        events.push(Event::Mouse(MouseEvent::CellLeft { cell }));
+                    }
+                    events.push(Event::Mouse(MouseEvent::CellEntered {
+                        cell: sprite_position,
+                    }));
+                    self.cell = Some(sprite_position);
+                }
+                if let Some(held) = self.held.as_mut() {
+                    if held.cell != sprite_position {
+                        if !held.dragging {
+                            held.dragging = true;
+                            events.push(Event::Mouse(MouseEvent::DragStart {
+                                button: held.button,
+                                cell: held.cell,
+                            }));
+                        }
+                        held.cell = sprite_position;
+                        events.push(Event::Mouse(MouseEvent::DragMove {
+                            button: held.button,
+                            cell: sprite_position,
+                        }));
+                    }
+                }
+            }
+            Event::Mouse(MouseEvent::CursorLeft) => {
+                if let Some(cell) = self.cell.take() {
+                    events.push(Event::Mouse(MouseEvent::CellLeft { cell }));
+                }
+                events.push(event);
+            }
+            // Backends report this for every movement outside the grid; only pass on the first.
+            Event::Mouse(MouseEvent::CursorLeftGrid) => {
+                if let Some(cell) = self.cell.take() {
+                    events.push(Event::Mouse(MouseEvent::CellLeft { cell }));
+                    events.push(event);
+                }
+            }
+            Event::Mouse(MouseEvent::Button {
+                state,
+                button,
+                modifiers,
+                ..
+            }) => {
+                events.push(Event::Mouse(MouseEvent::Button {
+                    state,
+                    button,
+                    cell: self.cell,
+                    modifiers,
+                }));
+                match (state, self.cell) {
+                    (ButtonState::Pressed, Some(cell)) => {
+                        if self.held.is_none() {
+                            self.held = Some(HeldButton {
+                                button,
+                                cell,
+                                dragging: false,
+                            });
+                        }
+                        match self.last_click {
+                            Some((b, c, t))
+                                if b == button
+                                    && c == cell
+                                    && now.duration_since(t) <= DOUBLE_CLICK_INTERVAL =>
+                            {
+                                self.last_click = None;
+                                events.push(Event::Mouse(MouseEvent::DoubleClick {
+                                    button,
+                                    cell,
+                                    modifiers,
+                                }));
+                            }
+                            _ => self.last_click = Some((button, cell, now)),
+                        }
+                    }
+                    (ButtonState::Pressed, None) => {}
+                    (ButtonState::Released, _) => {
+                        if let Some(held) = self.held.filter(|h| h.button == button) {
+                            self.held = None;
+                            if held.dragging {
+                                events.push(Event::Mouse(MouseEvent::DragEnd {
+                                    button,
+                                    cell: self.cell,
+                                }));
+                            }
+                        }
+                    }
+                }
+            }
+            Event::Mouse(MouseEvent::Wheel {
+                delta, modifiers, ..
+            }) => events.push(Event::Mouse(MouseEvent::Wheel {
+                delta,
+                cell: self.cell,
+                modifiers,
+            })),
+            _ => events.push(event),
+        }
+        events
+    }
+}
+
 /// KeyBinding is a specification for a keyboard shortcut.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct KeyBinding {
-    key: VirtualKeyCode,
+    key: Key,
     shift: bool,
     ctrl: bool,
     alt: bool,
@@ -186,7 +525,7 @@ pub struct KeyBinding {
 impl KeyBinding {
     /// Create a new KeyBinding. All modifiers are set to false initially; use
     /// `ctrl()`/`shift()`/`alt()`/`meta()` to build bindings that use modifiers.
-    pub fn new(key: VirtualKeyCode) -> Self {
+    pub fn new(key: Key) -> Self {
         KeyBinding {
             key,
             shift: false,
@@ -224,50 +563,99 @@ impl KeyBinding {
         self
     }
 
-    /// Returns `true` iff the provided raw keyboard event matches this binding.
-    pub fn matches(&self, event: winit::event::KeyboardInput) -> bool {
-        if event.state == winit::event::ElementState::Pressed {
-            #[allow(deprecated)]
-            match event.virtual_keycode {
-                None => false,
-                Some(vk) => {
-                    if vk == self.key {
-                        self.shift == event.modifiers.shift()
-                            && self.ctrl == event.modifiers.ctrl()
-                            && self.alt == event.modifiers.alt()
-                            && self.meta == event.modifiers.logo()
-                    } else {
-                        false
-                    }
-                }
-            }
-        } else {
-            false
+    /// Returns `true` iff the provided keyboard event matches this binding.
+    pub fn matches(&self, event: &KeyboardEvent) -> bool {
+        KeyBinding::try_from(*event) == Ok(*self)
+    }
+}
+
+impl std::convert::TryFrom<KeyboardEvent> for KeyBinding {
+    type Error = ();
+    /// Build the binding that a keyboard event corresponds to. Only key presses with a virtual
+    /// keycode can be converted.
+    fn try_from(event: KeyboardEvent) -> Result<Self, Self::Error> {
+        match event {
+            KeyboardEvent::KeyPress {
+                state: ButtonState::Pressed,
+                virtual_keycode: Some(key),
+                modifiers,
+                ..
+            } => Ok(KeyBinding {
+                key,
+                shift: modifiers.shift,
+                ctrl: modifiers.ctrl,
+                alt: modifiers.alt,
+                meta: modifiers.meta,
+            }),
+            _ => Err(()),
         }
     }
+}
 
-    // TODO: impl TryFrom instead.
-    fn try_from_event(event: winit::event::KeyboardInput) -> Option<KeyBinding> {
-        if event.state == winit::event::ElementState::Pressed {
-            #[allow(deprecated)]
-            event.virtual_keycode.map(|vk| KeyBinding {
-                key: vk,
-                shift: event.modifiers.shift(),
-                ctrl: event.modifiers.ctrl(),
-                alt: event.modifiers.alt(),
-                meta: event.modifiers.logo(),
-            })
-        } else {
-            None
+/// MouseBinding is a specification for a mouse button shortcut.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MouseBinding {
+    button: MouseButton,
+    shift: bool,
+    ctrl: bool,
+    alt: bool,
+    meta: bool,
+}
+
+impl MouseBinding {
+    /// Create a new MouseBinding. All modifiers are set to false initially; use
+    /// `ctrl()`/`shift()`/`alt()`/`meta()` to build bindings that use modifiers.
+    pub fn new(button: MouseButton) -> Self {
+        MouseBinding {
+            button,
+            shift: false,
+            ctrl: false,
+            alt: false,
+            meta: false,
         }
     }
+
+    /// Set `ctrl` to true.
+    #[must_use]
+    pub fn ctrl(mut self) -> Self {
+        self.ctrl = true;
+        self
+    }
+
+    /// Set `shift` to true.
+    #[must_use]
+    pub fn shift(mut self) -> Self {
+        self.shift = true;
+        self
+    }
+
+    /// Set `alt` to true.
+    #[must_use]
+    pub fn alt(mut self) -> Self {
+        self.alt = true;
+        self
+    }
+
+    /// Set `meta` to true.
+    #[must_use]
+    pub fn meta(mut self) -> Self {
+        self.meta = true;
+        self
+    }
 }
 
+// Callbacks that produce commands for mouse bindings.
+type MouseCommandFn<C> = Box<dyn Fn((u32, u32)) -> C + Send>;
+type WheelCommandFn<C> = Box<dyn Fn(ScrollDelta, Option<(u32, u32)>) -> C + Send>;
+
 /// Build input bindings based on message queues for multithreaded event handling.
 /// The type parameter `C` represents a "command" that is sent over the channel.
-/// TODO: mouse events
+///
+/// Mouse button and wheel bindings produce their command from the sprite cell under the cursor.
 pub struct EventDispatcher<C> {
     key_bindings: HashMap<KeyBinding, C>,
+    mouse_bindings: HashMap<MouseBinding, MouseCommandFn<C>>,
+    wheel_command: Option<WheelCommandFn<C>>,
     window_close_command: Option<C>,
     command_queue: std::sync::mpsc::SyncSender<C>,
 }
@@ -278,6 +666,24 @@ impl<C: Clone> EventDispatcher<C> {
         self.key_bindings.insert(key, command);
     }
 
+    /// Set a mouse button binding. When the button is pressed over a sprite cell, `command` is
+    /// called with the cell's position to produce the command to send.
+    pub fn bind_mouse<F>(&mut self, binding: MouseBinding, command: F)
+    where
+        F: Fn((u32, u32)) -> C + Send + 'static,
+    {
+        self.mouse_bindings.insert(binding, Box::new(command));
+    }
+
+    /// Set the command to send when the mouse wheel is scrolled. `command` is called with the
+    /// scroll amount and the sprite cell under the cursor, if any.
+    pub fn set_wheel_command<F>(&mut self, command: F)
+    where
+        F: Fn(ScrollDelta, Option<(u32, u32)>) -> C + Send + 'static,
+    {
+        self.wheel_command = Some(Box::new(command));
+    }
+
     /// Set command to send when the window is closed.
     pub fn set_window_close_command(&mut self, command: C) {
         self.window_close_command = Some(command);
@@ -287,99 +693,394 @@ impl<C: Clone> EventDispatcher<C> {
     pub fn new(command_queue: std::sync::mpsc::SyncSender<C>) -> Self {
         EventDispatcher {
             key_bindings: HashMap::<KeyBinding, C>::new(),
+            mouse_bindings: HashMap::new(),
+            wheel_command: None,
             window_close_command: None,
             command_queue,
         }
     }
 
-    /// Handle an event.
-    pub fn dispatch(&self, _event: &Event) -> Result<(), std::sync::mpsc::SendError<C>> {
-        /*
+    /// Handle an event, sending the bound command (if any) to the command queue.
+    pub fn dispatch(&self, event: &Event) -> Result<(), std::sync::mpsc::SendError<C>> {
         match event {
-            Event::KeyboardInput(ref ke) => {
-                if let Some(binding) = KeyBinding::try_from_event(*ke) {
+            Event::Keyboard(ke) => {
+                if let Ok(binding) = KeyBinding::try_from(*ke) {
                     if let Some(command) = self.key_bindings.get(&binding) {
                         self.command_queue.send(command.clone())?;
                     }
                 }
             }
-            Event::WindowDestroyed | Event::WindowCloseRequested => {
+            Event::Window(WindowEvent::Destroyed) | Event::Window(WindowEvent::CloseRequested) => {
                 if let Some(ref command) = self.window_close_command {
                     self.command_queue.send(command.clone())?;
                 }
             }
+            Event::Mouse(MouseEvent::Button {
+                state: ButtonState::Pressed,
+                button,
+                cell: Some(cell),
+                modifiers,
+            }) => {
+                let binding = MouseBinding {
+                    button: *button,
+                    shift: modifiers.shift,
+                    ctrl: modifiers.ctrl,
+                    alt: modifiers.alt,
+                    meta: modifiers.meta,
+                };
+                if let Some(command) = self.mouse_bindings.get(&binding) {
+                    self.command_queue.send(command(*cell))?;
+                }
+            }
+            Event::Mouse(MouseEvent::Wheel { delta, cell, .. }) => {
+                if let Some(ref command) = self.wheel_command {
+                    self.command_queue.send(command(*delta, *cell))?;
+                }
+            }
             _ => {}
         };
-        */
         Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    fn key_press(key: Key, modifiers: ModifiersState) -> KeyboardEvent {
+        KeyboardEvent::KeyPress {
+            scancode: 0,
+            state: ButtonState::Pressed,
+            virtual_keycode: Some(key),
+            modifiers,
+        }
+    }
+
+    #[test]
+    fn key_names_round_trip() {
+        for key in Key::ALL {
+            assert_eq!(key.to_string().parse::<Key>(), Ok(*key));
+        }
+        assert!("NotAKey".parse::<Key>().is_err());
+    }
+
     #[test]
     fn match_event() {
-        use super::*;
-        let kb = KeyBinding::new(VirtualKeyCode::Z);
-
-        assert!(kb.matches(
-            #[allow(deprecated)]
-            winit::event::KeyboardInput {
-                scancode: 0,
-                state: winit::event::ElementState::Pressed,
-                virtual_keycode: Some(VirtualKeyCode::Z),
-                modifiers: winit::event::ModifiersState::default()
-            }
-        ));
-
-        assert!(!kb.matches(
-            #[allow(deprecated)]
-            winit::event::KeyboardInput {
-                scancode: 0,
-                state: winit::event::ElementState::Pressed,
-                virtual_keycode: Some(VirtualKeyCode::X),
-                modifiers: winit::event::ModifiersState::default()
-            }
-        ));
-
-        assert!(!kb.matches(
-            #[allow(deprecated)]
-            winit::event::KeyboardInput {
-                scancode: 0,
-                state: winit::event::ElementState::Pressed,
-                virtual_keycode: Some(VirtualKeyCode::Z),
-                modifiers: winit::event::ModifiersState::SHIFT
-            }
-        ));
-
-        assert!(!kb.matches(
-            #[allow(deprecated)]
-            winit::event::KeyboardInput {
-                scancode: 0,
-                state: winit::event::ElementState::Pressed,
-                virtual_keycode: Some(VirtualKeyCode::Z),
-                modifiers: winit::event::ModifiersState::CTRL
-            }
-        ));
-
-        assert!(!kb.matches(
-            #[allow(deprecated)]
-            winit::event::KeyboardInput {
-                scancode: 0,
-                state: winit::event::ElementState::Pressed,
-                virtual_keycode: Some(VirtualKeyCode::Z),
-                modifiers: winit::event::ModifiersState::ALT
+        let kb = KeyBinding::new(Key::Z);
+
+        assert!(kb.matches(&key_press(Key::Z, Default::default())));
+        assert!(!kb.matches(&key_press(Key::X, Default::default())));
+
+        for modifiers in [
+            ModifiersState {
+                shift: true,
+                ..Default::default()
+            },
+            ModifiersState {
+                ctrl: true,
+                ..Default::default()
+            },
+            ModifiersState {
+                alt: true,
+                ..Default::default()
+            },
+            ModifiersState {
+                meta: true,
+                ..Default::default()
+            },
+        ] {
+            assert!(!kb.matches(&key_press(Key::Z, modifiers)));
+        }
+
+        assert!(!kb.matches(&KeyboardEvent::KeyPress {
+            scancode: 0,
+            state: ButtonState::Released,
+            virtual_keycode: Some(Key::Z),
+            modifiers: Default::default(),
+        }));
+        assert!(!kb.matches(&KeyboardEvent::Character('z')));
+    }
+
+    #[test]
+    fn match_event_with_modifiers() {
+        let kb = KeyBinding::new(Key::S).ctrl().shift();
+        assert!(kb.matches(&key_press(
+            Key::S,
+            ModifiersState {
+                ctrl: true,
+                shift: true,
+                ..Default::default()
             }
-        ));
-
-        assert!(!kb.matches(
-            #[allow(deprecated)]
-            winit::event::KeyboardInput {
-                scancode: 0,
-                state: winit::event::ElementState::Pressed,
-                virtual_keycode: Some(VirtualKeyCode::Z),
-                modifiers: winit::event::ModifiersState::LOGO
+        )));
+        assert!(!kb.matches(&key_press(
+            Key::S,
+            ModifiersState {
+                ctrl: true,
+                ..Default::default()
             }
-        ));
+        )));
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum Command {
+        Quit,
+        Save,
+        Click((u32, u32)),
+        Inspect((u32, u32)),
+        Scroll(ScrollDelta, Option<(u32, u32)>),
+    }
+
+    fn dispatcher() -> (EventDispatcher<Command>, std::sync::mpsc::Receiver<Command>) {
+        let (tx, rx) = std::sync::mpsc::sync_channel(16);
+        let mut d = EventDispatcher::new(tx);
+        d.bind(KeyBinding::new(Key::S).ctrl(), Command::Save);
+        d.set_window_close_command(Command::Quit);
+        d.bind_mouse(MouseBinding::new(MouseButton::Left), Command::Click);
+        d.bind_mouse(
+            MouseBinding::new(MouseButton::Left).shift(),
+            Command::Inspect,
+        );
+        d.set_wheel_command(Command::Scroll);
+        (d, rx)
+    }
+
+    fn button(state: ButtonState, cell: Option<(u32, u32)>, modifiers: ModifiersState) -> Event {
+        Event::Mouse(MouseEvent::Button {
+            state,
+            button: MouseButton::Left,
+            cell,
+            modifiers,
+        })
+    }
+
+    fn cursor_moved(x: u32, y: u32) -> Event {
+        Event::Mouse(MouseEvent::CursorMoved {
+            sprite_position: (x, y),
+            absolute_position: (0.0, 0.0),
+        })
+    }
+
+    #[test]
+    fn dispatch_key() {
+        let (d, rx) = dispatcher();
+        let ctrl = ModifiersState {
+            ctrl: true,
+            ..Default::default()
+        };
+        d.dispatch(&Event::Keyboard(key_press(Key::S, ctrl)))
+            .unwrap();
+        d.dispatch(&Event::Keyboard(key_press(Key::S, Default::default())))
+            .unwrap();
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![Command::Save]);
+    }
+
+    #[test]
+    fn dispatch_window_close() {
+        let (d, rx) = dispatcher();
+        d.dispatch(&Event::Window(WindowEvent::CloseRequested))
+            .unwrap();
+        d.dispatch(&Event::Window(WindowEvent::Destroyed)).unwrap();
+        d.dispatch(&Event::Window(WindowEvent::Focused(false)))
+            .unwrap();
+        assert_eq!(
+            rx.try_iter().collect::<Vec<_>>(),
+            vec![Command::Quit, Command::Quit]
+        );
+    }
+
+    #[test]
+    fn dispatch_mouse_button() {
+        let (d, rx) = dispatcher();
+        let shift = ModifiersState {
+            shift: true,
+            ..Default::default()
+        };
+        // Presses outside the grid have no cell to report.
+        d.dispatch(&button(ButtonState::Pressed, None, Default::default()))
+            .unwrap();
+        d.dispatch(&button(
+            ButtonState::Pressed,
+            Some((3, 4)),
+            Default::default(),
+        ))
+        .unwrap();
+        d.dispatch(&button(
+            ButtonState::Released,
+            Some((3, 4)),
+            Default::default(),
+        ))
+        .unwrap();
+        d.dispatch(&button(ButtonState::Pressed, Some((5, 1)), shift))
+            .unwrap();
+        assert_eq!(
+            rx.try_iter().collect::<Vec<_>>(),
+            vec![Command::Click((3, 4)), Command::Inspect((5, 1))]
+        );
+    }
+
+    #[test]
+    fn dispatch_wheel() {
+        let (d, rx) = dispatcher();
+        for cell in [None, Some((2, 2))] {
+            d.dispatch(&Event::Mouse(MouseEvent::Wheel {
+                delta: ScrollDelta::Lines(0.0, 1.0),
+                cell,
+                modifiers: Default::default(),
+            }))
+            .unwrap();
+        }
+        assert_eq!(
+            rx.try_iter().collect::<Vec<_>>(),
+            vec![
+                Command::Scroll(ScrollDelta::Lines(0.0, 1.0), None),
+                Command::Scroll(ScrollDelta::Lines(0.0, 1.0), Some((2, 2))),
+            ]
+        );
+    }
+
+    #[test]
+    fn tracker_annotates_cell() {
+        let mut t = MouseTracker::default();
+        let now = std::time::Instant::now();
+        let press = button(ButtonState::Pressed, None, Default::default());
+        assert_eq!(t.process(press, now), vec![press]);
+        t.process(cursor_moved(3, 4), now);
+        assert_eq!(
+            t.process(press, now),
+            vec![button(
+                ButtonState::Pressed,
+                Some((3, 4)),
+                Default::default()
+            )]
+        );
+        t.process(Event::Mouse(MouseEvent::CursorLeft), now);
+        assert_eq!(
+            t.process(
+                Event::Mouse(MouseEvent::Wheel {
+                    delta: ScrollDelta::Lines(0.0, 1.0),
+                    cell: Some((1, 1)),
+                    modifiers: Default::default(),
+                }),
+                now
+            ),
+            vec![Event::Mouse(MouseEvent::Wheel {
+                delta: ScrollDelta::Lines(0.0, 1.0),
+                cell: None,
+                modifiers: Default::default(),
+            })]
+        );
+    }
+
+    #[test]
+    fn tracker_drag() {
+        let mut t = MouseTracker::default();
+        let now = std::time::Instant::now();
+        t.process(cursor_moved(1, 1), now);
+        t.process(button(ButtonState::Pressed, None, Default::default()), now);
+        // Moving within the pressed cell doesn't start a drag.
+        assert_eq!(t.process(cursor_moved(1, 1), now), vec![cursor_moved(1, 1)]);
+        assert_eq!(
+            t.process(cursor_moved(2, 1), now),
+            vec![
+                cursor_moved(2, 1),
+                Event::Mouse(MouseEvent::CellLeft { cell: (1, 1) }),
+                Event::Mouse(MouseEvent::CellEntered { cell: (2, 1) }),
+                Event::Mouse(MouseEvent::DragStart {
+                    button: MouseButton::Left,
+                    cell: (1, 1),
+                }),
+                Event::Mouse(MouseEvent::DragMove {
+                    button: MouseButton::Left,
+                    cell: (2, 1),
+                }),
+            ]
+        );
+        assert_eq!(
+            t.process(cursor_moved(2, 2), now),
+            vec![
+                cursor_moved(2, 2),
+                Event::Mouse(MouseEvent::CellLeft { cell: (2, 1) }),
+                Event::Mouse(MouseEvent::CellEntered { cell: (2, 2) }),
+                Event::Mouse(MouseEvent::DragMove {
+                    button: MouseButton::Left,
+                    cell: (2, 2),
+                }),
+            ]
+        );
+        assert_eq!(
+            t.process(button(ButtonState::Released, None, Default::default()), now),
+            vec![
+                button(ButtonState::Released, Some((2, 2)), Default::default()),
+                Event::Mouse(MouseEvent::DragEnd {
+                    button: MouseButton::Left,
+                    cell: Some((2, 2)),
+                }),
+            ]
+        );
+        // The drag is over.
+        assert!(!t.process(cursor_moved(3, 3), now).iter().any(|e| matches!(
+            e,
+            Event::Mouse(MouseEvent::DragMove { .. } | MouseEvent::DragEnd { .. })
+        )));
+    }
+
+    #[test]
+    fn tracker_cell_hover() {
+        let mut t = MouseTracker::default();
+        let now = std::time::Instant::now();
+        let left_grid = Event::Mouse(MouseEvent::CursorLeftGrid);
+        assert_eq!(
+            t.process(cursor_moved(1, 1), now),
+            vec![
+                cursor_moved(1, 1),
+                Event::Mouse(MouseEvent::CellEntered { cell: (1, 1) }),
+            ]
+        );
+        // Movement within a cell produces no hover events.
+        assert_eq!(t.process(cursor_moved(1, 1), now), vec![cursor_moved(1, 1)]);
+        assert_eq!(
+            t.process(left_grid, now),
+            vec![
+                Event::Mouse(MouseEvent::CellLeft { cell: (1, 1) }),
+                left_grid,
+            ]
+        );
+        assert_eq!(t.process(left_grid, now), vec![]);
+        t.process(cursor_moved(0, 1), now);
+        assert_eq!(
+            t.process(Event::Mouse(MouseEvent::CursorLeft), now),
+            vec![
+                Event::Mouse(MouseEvent::CellLeft { cell: (0, 1) }),
+                Event::Mouse(MouseEvent::CursorLeft),
+            ]
+        );
+    }
+
+    #[test]
+    fn tracker_double_click() {
+        let mut t = MouseTracker::default();
+        let start = std::time::Instant::now();
+        let press = button(ButtonState::Pressed, None, Default::default());
+        let release = button(ButtonState::Released, None, Default::default());
+        let double_click = Event::Mouse(MouseEvent::DoubleClick {
+            button: MouseButton::Left,
+            cell: (1, 1),
+            modifiers: Default::default(),
+        });
+        t.process(cursor_moved(1, 1), start);
+        t.process(press, start);
+        t.process(release, start);
+        let events = t.process(press, start + std::time::Duration::from_millis(200));
+        assert_eq!(events.last(), Some(&double_click));
+        t.process(release, start);
+
+        // A third click starts a new sequence rather than registering another double click.
+        let events = t.process(press, start + std::time::Duration::from_millis(300));
+        assert_eq!(events.len(), 1);
+        t.process(release, start);
+
+        // Too slow.
+        let events = t.process(press, start + std::time::Duration::from_secs(1));
+        assert_eq!(events.len(), 1);
     }
 }
@@ -18,16 +18,9 @@
 //! # Roadmap
 //! ## Features to be implemented
 //!
-//! * Input handling
-//!   * Don't use winit event types in the public interface
 //! * GUI library
 //!   * Splash screen support
 //! * Serialization/persistence framework
-//! * Graphics improvements
-//!   * User-specified shaders
-//!   * Animated sprites
-//!   * Hotswapping fonts
-//!   * Dynamic zoom
 //! * Resource management system
 //!   * Build sprite map textures at runtime
 //! * Audio
@@ -36,6 +29,8 @@
 #![recursion_limit = "72"]
 #![deny(missing_docs)]
 #![allow(dead_code)]
+// `is_multiple_of()` needs Rust 1.87, which is newer than the crate otherwise requires.
+#![allow(clippy::manual_is_multiple_of)]
 
 /// Routines for creating and managing the game window.
 pub mod window;
@@ -52,6 +47,15 @@ pub mod input;
 /// Functionality for building in-game UIs.
 pub mod ui;
 
+/// Terminal output backend, for running games over SSH or in a console.
+#[cfg(feature = "terminal")]
+pub mod terminal;
+
+/// Support for testing drivers without opening a window.
+pub mod testing;
+
+mod timestep;
+
 use log::debug;
 
 /// Signals to indicate whether the engine should keep running or halt.
@@ -87,8 +91,14 @@ where
 {
     window: window::Window,
     driver: D,
+    timestep: timestep::FixedTimestep,
 }
 
+/// Default number of `Driver::update()` ticks per second.
+const DEFAULT_TICK_RATE: u32 = 60;
+/// Default maximum number of ticks run in a single frame when the simulation falls behind.
+const DEFAULT_MAX_CATCH_UP_STEPS: u32 = 5;
+
 impl<D> Engine<D>
 where
     D: Driver + 'static,
@@ -101,109 +111,219 @@ where
         Ok(Engine {
             window: window_builder.build()?,
             driver,
+            timestep: timestep::FixedTimestep::new(DEFAULT_TICK_RATE, DEFAULT_MAX_CATCH_UP_STEPS),
         })
     }
 
-    /// Run the main loop until one of the library hooks tells us to quit.
+    /// Set the number of times per second `Driver::update()` is called. Defaults to 60.
+    #[must_use]
+    pub fn with_tick_rate(mut self, ticks_per_second: u32) -> Self {
+        self.timestep.set_rate(ticks_per_second);
+
+        self
+    }
+
+    /// Set the maximum number of `Driver::update()` ticks run before a single frame is drawn,
+    /// when the simulation has fallen behind. Any time beyond that is dropped, so the game slows
+    /// down instead of freezing while it catches up. Defaults to 5.
+    #[must_use]
+    pub fn with_max_catch_up_steps(mut self, steps: u32) -> Self {
+        self.timestep.set_max_steps(steps);
+
+        self
+    }
+
+    /// Run the main loop until one of the library hooks tells us to quit. This never returns;
+    /// the process exits once the driver halts (after calling `Driver::on_exit()`). See
+    /// `run_return()` for a variant that hands the driver back.
     pub fn run(self) -> ! {
-        let (window, mut driver) = (self.window, self.driver);
-        window.window.set_visible(true);
+        let (event_loop, mut state) = self.into_parts();
+        event_loop.run(move |event, _, control_flow| state.handle_event(event, control_flow))
+    }
 
-        let (width, height, mut renderer, winit_window, event_loop) = (
-            window.width,
-            window.height,
-            window.renderer,
-            window.window,
+    /// Run the main loop until one of the library hooks tells us to quit, then return the driver
+    /// so that it can be inspected or reused. `Driver::on_exit()` is called before this returns.
+    ///
+    /// This is only available on desktop platforms (and Android), since others can't return from
+    /// their event loop.
+    #[cfg(any(
+        target_os = "windows",
+        target_os = "macos",
+        target_os = "android",
+        target_os = "linux",
+        target_os = "dragonfly",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "openbsd"
+    ))]
+    pub fn run_return(self) -> D {
+        use winit::platform::run_return::EventLoopExtRunReturn;
+
+        let (mut event_loop, mut state) = self.into_parts();
+        event_loop.run_return(|event, _, control_flow| state.handle_event(event, control_flow));
+        state.driver
+    }
+
+    fn into_parts(self) -> (winit::event_loop::EventLoop<()>, EventLoopState<D>) {
+        let window = self.window;
+        window.window.set_visible(true);
+        let sprite_dimensions = window.renderer.sprite_dimensions();
+        (
             window.event_loop,
-        );
-        let winit_window_id = winit_window.id();
-        event_loop.run(move |event, _, control_flow| {
-            match event {
-                winit::event::Event::RedrawRequested(_) => {
-                    renderer.render_frame().unwrap();
-                }
-                winit::event::Event::WindowEvent {
-                    ref event,
-                    window_id,
-                } => {
-                    if window_id == winit_window_id {
-                        //debug!("{:?}", event);
-                        match event {
-                            winit::event::WindowEvent::Resized(physical_size) => {
-                                renderer.resize(*physical_size);
-                            }
-                            winit::event::WindowEvent::ScaleFactorChanged {
-                                new_inner_size,
-                                ..
-                            } => {
-                                renderer.resize(**new_inner_size);
-                            }
-                            winit::event::WindowEvent::CursorMoved { position, .. } => {
-                                let (ax, ay) = renderer.aspect_ratio;
-                                let winit::dpi::PhysicalPosition { x: xf, y: yf } = position;
-                                let (x, y) = (*xf as u32, *yf as u32);
-                                let winit::dpi::PhysicalSize {
-                                    width: screen_w,
-                                    height: screen_h,
-                                } = winit_window.inner_size(); // = size.to_physical(scale_factor);
-
-                                let target_w = std::cmp::min(screen_w, (screen_h * ax) / ay);
-                                let target_h = std::cmp::min(screen_h, (screen_w * ay) / ax);
-                                let offs_x;
-                                let offs_y;
-                                if target_w < screen_w {
-                                    offs_x = (screen_w - target_w) / 2;
-                                    offs_y = 0;
-                                } else {
-                                    // target_h < screen_h
-                                    offs_x = 0;
-                                    offs_y = (screen_h - target_h) / 2;
-                                }
-
-                                if x > offs_x
-                                    && y > offs_y
-                                    && x < offs_x + target_w
-                                    && y < offs_y + target_h
-                                {
-                                    let xp = (x - offs_x) as f32;
-                                    let yp = (y - offs_y) as f32;
-
-                                    let sx = target_w as f32 / width as f32;
-                                    let sy = target_h as f32 / height as f32;
-
-                                    let xs = (xp / sx) as u32;
-                                    let ys = (yp / sy) as u32;
-
-                                    let e = input::Event::Mouse(input::MouseEvent::CursorMoved {
-                                        sprite_position: (xs, ys),
-                                        absolute_position: (*xf, *yf),
-                                    });
-                                    debug!("{:?}", e);
-                                    if driver.handle_input(e) == EngineSignal::Halt {
-                                        *control_flow = winit::event_loop::ControlFlow::Exit;
-                                    }
-                                }
-                            }
-                            _ => {}
-                        }
+            EventLoopState {
+                driver: self.driver,
+                renderer: window.renderer,
+                window: window.window,
+                width: window.width,
+                height: window.height,
+                resizable_grid: window.resizable_grid,
+                sprite_dimensions,
+                timestep: self.timestep,
+                mouse_tracker: Default::default(),
+            },
+        )
+    }
+}
+
+/// Everything the main loop needs while it runs, apart from the event loop itself.
+struct EventLoopState<D> {
+    driver: D,
+    renderer: graphics::render::Renderer,
+    window: winit::window::Window,
+    // Width & height of the window (in sprites).
+    width: u32,
+    height: u32,
+    resizable_grid: bool,
+    // Size of a sprite as of the last frame, to notice when the sprite texture is replaced.
+    sprite_dimensions: (u32, u32),
+    timestep: timestep::FixedTimestep,
+    mouse_tracker: input::MouseTracker,
+}
+
+impl<D> EventLoopState<D>
+where
+    D: Driver,
+{
+    fn window_resized(&mut self, size: winit::dpi::PhysicalSize<u32>) {
+        self.renderer.resize(size);
+        if self.resizable_grid {
+            let (sprite_w, sprite_h) = self.renderer.sprite_dimensions();
+            self.renderer
+                .resize_grid((size.width / sprite_w, size.height / sprite_h));
+            (self.width, self.height) = self.renderer.dimensions;
+        }
+        self.driver
+            .on_resize((size.width, size.height), (self.width, self.height));
+    }
+
+    /// Adapt the window after the driver replaces the sprite texture with one whose sprites are
+    /// a different size.
+    fn sprite_texture_changed(&mut self) {
+        let sprite_dimensions = self.renderer.sprite_dimensions();
+        if sprite_dimensions == self.sprite_dimensions {
+            return;
+        }
+        self.sprite_dimensions = sprite_dimensions;
+        if self.resizable_grid {
+            // Keep the window, and fit as many of the new cells into it as possible.
+            self.window_resized(self.window.inner_size());
+        } else {
+            // Keep the grid, and resize the window to show it unscaled. This generates a resize
+            // event, which takes care of the rest.
+            let (width, height) = self.renderer.pixel_dimensions;
+            self.window
+                .set_inner_size(winit::dpi::PhysicalSize::new(width, height));
+        }
+    }
+
+    fn handle_event(
+        &mut self,
+        event: winit::event::Event<'_, ()>,
+        control_flow: &mut winit::event_loop::ControlFlow,
+    ) {
+        let mut events = vec![];
+        match event {
+            winit::event::Event::RedrawRequested(_) => {
+                self.renderer.render_frame().unwrap();
+            }
+            winit::event::Event::WindowEvent {
+                ref event,
+                window_id,
+            } if window_id == self.window.id() => {
+                //debug!("{:?}", event);
+                match event {
+                    winit::event::WindowEvent::Resized(physical_size) => {
+                        self.window_resized(*physical_size);
                     }
-                }
-                winit::event::Event::MainEventsCleared => {
-                    if driver.process_frame(&mut renderer) == EngineSignal::Halt {
-                        *control_flow = winit::event_loop::ControlFlow::Exit;
+                    winit::event::WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                        self.window_resized(**new_inner_size);
                     }
-
-                    winit_window.request_redraw();
+                    winit::event::WindowEvent::Focused(focused) => {
+                        self.driver.on_focus_changed(*focused);
+                    }
+                    winit::event::WindowEvent::CursorMoved { position, .. } => {
+                        let absolute_position = (position.x, position.y);
+                        events.push(input::Event::Mouse(
+                            match self.renderer.screen_to_cell(absolute_position) {
+                                Some(sprite_position) => input::MouseEvent::CursorMoved {
+                                    sprite_position,
+                                    absolute_position,
+                                },
+                                None => input::MouseEvent::CursorLeftGrid,
+                            },
+                        ));
+                    }
+                    _ => {}
                 }
-                _ => {}
             }
-            if let Ok(e) = std::convert::TryInto::<input::Event>::try_into(event) {
-                debug!("{:?}", e);
-                if driver.handle_input(e) == EngineSignal::Halt {
+            winit::event::Event::MainEventsCleared => {
+                let mut signal = EngineSignal::Continue;
+                for _ in 0..self.timestep.advance(std::time::Instant::now()) {
+                    signal.update(self.driver.update(self.timestep.tick()));
+                    if signal == EngineSignal::Halt {
+                        break;
+                    }
+                }
+                // Don't draw another frame once the driver has asked to halt.
+                if signal == EngineSignal::Continue {
+                    let process_start = std::time::Instant::now();
+                    signal.update(
+                        self.driver
+                            .process_frame_interpolated(&mut self.renderer, self.timestep.alpha()),
+                    );
+                    self.renderer.record_process_time(process_start.elapsed());
+                }
+                if signal == EngineSignal::Halt {
                     *control_flow = winit::event_loop::ControlFlow::Exit;
                 }
+                self.sprite_texture_changed();
+
+                self.window.request_redraw();
             }
-        })
+            winit::event::Event::NewEvents(winit::event::StartCause::Init) => {
+                self.driver.on_start(&mut self.renderer);
+                self.sprite_texture_changed();
+            }
+            winit::event::Event::Suspended => self.driver.on_suspend(),
+            winit::event::Event::Resumed => self.driver.on_resume(),
+            winit::event::Event::LoopDestroyed => {
+                self.driver.on_exit();
+            }
+            _ => {}
+        }
+        if let Ok(e) = std::convert::TryInto::<input::Event>::try_into(event) {
+            events.push(e);
+        }
+        let now = std::time::Instant::now();
+        for e in events
+            .into_iter()
+            .flat_map(|e| self.mouse_tracker.process(e, now))
+        {
+            debug!("{:?}", e);
+            if self.driver.handle_input(e) == EngineSignal::Halt {
+                *control_flow = winit::event_loop::ControlFlow::Exit;
+            }
+        }
     }
 }
 
@@ -222,4 +342,293 @@ pub trait Driver {
     fn process_frame<R>(&mut self, renderer: &mut R) -> EngineSignal
     where
         R: graphics::render::RenderInterface;
+
+    /// Client hook for advancing the game simulation. Unlike `process_frame()`, this is called at
+    /// a fixed rate (see `Engine::with_tick_rate()`) independent of the display, with `dt` set to
+    /// the length of one tick. Any ticks that are due run before the frame is drawn.
+    #[must_use]
+    fn update(&mut self, _dt: std::time::Duration) -> EngineSignal {
+        EngineSignal::Continue
+    }
+
+    /// Client hook called once when the main loop starts, before any events are delivered. This is
+    /// a good place to set up layers or draw the first frame.
+    fn on_start<R>(&mut self, _renderer: &mut R)
+    where
+        R: graphics::render::RenderInterface,
+    {
+    }
+
+    /// Client hook called when the window is resized or its scale factor changes. `pixels` is the
+    /// new size of the window's drawable area, and `cells` is the size of the sprite grid.
+    fn on_resize(&mut self, _pixels: (u32, u32), _cells: (u32, u32)) {}
+
+    /// Client hook called when the window gains (`true`) or loses (`false`) keyboard focus. A
+    /// `WindowEvent::Focused` input event is still delivered as well.
+    fn on_focus_changed(&mut self, _focused: bool) {}
+
+    /// Client hook called when the application is suspended, e.g. sent to the background on
+    /// mobile platforms. Games may want to pause here.
+    fn on_suspend(&mut self) {}
+
+    /// Client hook called when the application is resumed after being suspended.
+    fn on_resume(&mut self) {}
+
+    /// Client hook called once when the main loop shuts down, e.g. to save the game. It is called
+    /// whether the driver halted or the loop ended for some other reason.
+    fn on_exit(&mut self) {}
+
+    /// Variant of `process_frame()` that also receives `alpha`: how far the current frame lies
+    /// between the last `update()` tick and the next one, in `[0, 1)`. Drivers can use this to
+    /// interpolate movement between ticks. By default this just calls `process_frame()`.
+    #[must_use]
+    fn process_frame_interpolated<R>(&mut self, renderer: &mut R, _alpha: f32) -> EngineSignal
+    where
+        R: graphics::render::RenderInterface,
+    {
+        self.process_frame(renderer)
+    }
+}
+
+/// Transitions a `State` can request from its `StateMachine`.
+pub enum Transition {
+    /// Stay in the current state.
+    None,
+    /// Suspend the current state and make a new state the active one.
+    Push(Box<dyn State>),
+    /// Leave the current state, returning to the one below it. Popping the last state halts the
+    /// engine.
+    Pop,
+    /// Leave the current state and make a new state the active one in its place.
+    Replace(Box<dyn State>),
+    /// Halt the engine.
+    Quit,
+}
+
+/// A single screen or mode of a game, e.g. the title screen, the main game, or an inventory
+/// popup. States are managed by a `StateMachine`; only the state on top of its stack receives
+/// input and updates.
+pub trait State {
+    /// Handle an input event, see `Driver::handle_input()`.
+    fn handle_input(&mut self, event: input::Event) -> Transition;
+
+    /// Advance the state by one fixed tick, see `Driver::update()`.
+    fn update(&mut self, _dt: std::time::Duration) -> Transition {
+        Transition::None
+    }
+
+    /// Draw the state. The layer is cleared before the bottommost visible state is drawn.
+    fn render(&mut self, layer: &mut graphics::drawing::SpriteLayer);
+
+    /// Whether the states below this one stay visible while it is active. Overlay states (e.g.
+    /// popups) are rendered on top of the state below them. Defaults to `false`.
+    fn is_overlay(&self) -> bool {
+        false
+    }
+}
+
+/// A `Driver` that manages a stack of `State`s. Input and updates go to the state on top of the
+/// stack, and the transitions it returns push, pop or replace states. Rendering starts from the
+/// topmost state that isn't an overlay, so popups can be drawn over the screen that opened them.
+pub struct StateMachine {
+    states: Vec<Box<dyn State>>,
+    layer: graphics::drawing::SpriteLayer,
+}
+
+impl StateMachine {
+    /// Create a new `StateMachine` that renders into a grid of the given width and height
+    /// (measured in cells), starting in `initial`.
+    pub fn new(width: usize, height: usize, initial: Box<dyn State>) -> Self {
+        StateMachine {
+            states: vec![initial],
+            layer: graphics::drawing::SpriteLayer::new(width, height),
+        }
+    }
+
+    /// Number of states on the stack.
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    /// Returns `true` iff there are no states left, i.e. the machine has halted.
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    fn apply(&mut self, transition: Transition) -> EngineSignal {
+        match transition {
+            Transition::None => {}
+            Transition::Push(state) => self.states.push(state),
+            Transition::Pop => {
+                self.states.pop();
+            }
+            Transition::Replace(state) => {
+                self.states.pop();
+                self.states.push(state);
+            }
+            Transition::Quit => self.states.clear(),
+        }
+        if self.states.is_empty() {
+            EngineSignal::Halt
+        } else {
+            EngineSignal::Continue
+        }
+    }
+}
+
+impl Driver for StateMachine {
+    fn on_resize(&mut self, _pixels: (u32, u32), cells: (u32, u32)) {
+        self.layer.resize(cells.0 as usize, cells.1 as usize);
+    }
+
+    fn handle_input(&mut self, event: input::Event) -> EngineSignal {
+        match self.states.last_mut() {
+            Some(state) => {
+                let transition = state.handle_input(event);
+                self.apply(transition)
+            }
+            None => EngineSignal::Halt,
+        }
+    }
+
+    fn update(&mut self, dt: std::time::Duration) -> EngineSignal {
+        match self.states.last_mut() {
+            Some(state) => {
+                let transition = state.update(dt);
+                self.apply(transition)
+            }
+            None => EngineSignal::Halt,
+        }
+    }
+
+    fn process_frame<R>(&mut self, renderer: &mut R) -> EngineSignal
+    where
+        R: graphics::render::RenderInterface,
+    {
+        if self.states.is_empty() {
+            return EngineSignal::Halt;
+        }
+        let bottom = self
+            .states
+            .iter()
+            .rposition(|s| !s.is_overlay())
+            .unwrap_or(0);
+        self.layer.clear();
+        for state in self.states[bottom..].iter_mut() {
+            state.render(&mut self.layer);
+        }
+        renderer.update(self.layer.iter());
+        EngineSignal::Continue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::drawing::SpriteLayer;
+    use crate::graphics::render::{RenderInterface, SoftwareRenderer};
+    use crate::resources::sprite::SpriteTexture;
+    use crate::testing::TestHarness;
+
+    /// Draws its sprite into the cell at `x`. Characters trigger transitions.
+    struct TestState {
+        x: usize,
+        sprite: u32,
+        overlay: bool,
+    }
+
+    impl State for TestState {
+        fn handle_input(&mut self, event: input::Event) -> Transition {
+            let state = |x, sprite, overlay| Box::new(TestState { x, sprite, overlay });
+            match event {
+                input::Event::Keyboard(input::KeyboardEvent::Character(c)) => match c {
+                    'p' => Transition::Push(state(1, 2, true)),
+                    'r' => Transition::Replace(state(0, 3, false)),
+                    'x' => Transition::Pop,
+                    'q' => Transition::Quit,
+                    _ => Transition::None,
+                },
+                _ => Transition::None,
+            }
+        }
+
+        fn render(&mut self, layer: &mut SpriteLayer) {
+            layer[self.x].sprite = self.sprite;
+        }
+
+        fn is_overlay(&self) -> bool {
+            self.overlay
+        }
+    }
+
+    fn harness() -> TestHarness<StateMachine, SoftwareRenderer> {
+        let tex = SpriteTexture::new_from_pixels(&[0; 4], 4, 1, 1, 1, 4).unwrap();
+        TestHarness::new(
+            StateMachine::new(
+                2,
+                1,
+                Box::new(TestState {
+                    x: 0,
+                    sprite: 1,
+                    overlay: false,
+                }),
+            ),
+            SoftwareRenderer::new(2, 1, &tex),
+        )
+    }
+
+    fn key(c: char) -> input::Event {
+        input::Event::Keyboard(input::KeyboardEvent::Character(c))
+    }
+
+    fn sprites(h: &TestHarness<StateMachine, SoftwareRenderer>) -> Vec<u32> {
+        h.layer(0).iter().map(|c| c.sprite).collect()
+    }
+
+    #[test]
+    fn overlay_renders_over_lower_state() {
+        let mut h = harness();
+        h.run_frames(1).unwrap();
+        assert_eq!(sprites(&h), vec![1, 0]);
+
+        assert_eq!(h.send_events([key('p')]), EngineSignal::Continue);
+        h.run_frames(1).unwrap();
+        assert_eq!(h.driver().len(), 2);
+        assert_eq!(sprites(&h), vec![1, 2]);
+
+        // Replacing the popup with an opaque state hides the bottom one.
+        h.send_events([key('r')]);
+        h.run_frames(1).unwrap();
+        assert_eq!(h.driver().len(), 2);
+        assert_eq!(sprites(&h), vec![3, 0]);
+
+        h.send_events([key('x')]);
+        h.run_frames(1).unwrap();
+        assert_eq!(sprites(&h), vec![1, 0]);
+    }
+
+    #[test]
+    fn pop_last_state_halts() {
+        let mut h = harness();
+        assert_eq!(h.send_events([key('x')]), EngineSignal::Halt);
+        assert!(h.driver().is_empty());
+        assert_eq!(h.run_frames(1).unwrap(), EngineSignal::Halt);
+    }
+
+    #[test]
+    fn resize_grid() {
+        let mut h = harness();
+        h.resize_grid(3, 2);
+        h.run_frames(1).unwrap();
+        assert_eq!(h.renderer().dimensions(), (3, 2));
+        assert_eq!(sprites(&h), vec![1, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn quit_halts() {
+        let mut h = harness();
+        h.send_events([key('p')]);
+        assert_eq!(h.send_events([key('q')]), EngineSignal::Halt);
+        assert!(h.driver().is_empty());
+    }
 }
@@ -91,8 +91,8 @@ impl Color {
     /// ```
     pub fn from_hsv(hue: f32, saturation: f32, value: f32) -> Self {
         let hh = ((hue % 360.0) + if hue < 0.0 { 360.0 } else { 0.0 }) / 60.0;
-        let ss = saturation.min(1.0).max(0.0);
-        let vv = value.min(1.0).max(0.0);
+        let ss = saturation.clamp(0.0, 1.0);
+        let vv = value.clamp(0.0, 1.0);
 
         let chroma = vv * ss;
         let secondary = chroma * (1.0 - (hh % 2.0 - 1.0).abs());
@@ -187,8 +187,8 @@ impl Color {
     /// ```
     pub fn from_hsl(hue: f32, saturation: f32, lightness: f32) -> Self {
         let hh = ((hue % 360.0) + if hue < 0.0 { 360.0 } else { 0.0 }) / 60.0;
-        let ss = saturation.min(1.0).max(0.0);
-        let ll = lightness.min(1.0).max(0.0);
+        let ss = saturation.clamp(0.0, 1.0);
+        let ll = lightness.clamp(0.0, 1.0);
 
         let chroma = (1.0 - (2.0 * ll - 1.0).abs()) * ss;
         let secondary = chroma * (1.0 - (hh % 2.0 - 1.0).abs());
@@ -629,9 +629,10 @@ impl ProceduralPalette {
 }
 
 /// ColorExpression is a way to represent a conversion from one color to another.
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub enum ColorExpression {
     /// Null transform: do nothing and just copy the underlying palette at the same index.
+    #[default]
     Null,
     /// Straight copy of a different palette index.
     PaletteMap(PaletteIndex),
@@ -664,12 +665,6 @@ impl ColorExpression {
     }
 }
 
-impl Default for ColorExpression {
-    fn default() -> Self {
-        ColorExpression::Null
-    }
-}
-
 /// Expression representing a single channel.
 ///
 /// ```
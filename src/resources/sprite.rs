@@ -196,8 +196,7 @@ mod tests {
             unimplemented!();
         }
         fn iter(&self) -> Self::Iter {
-            let v: Vec<Sprite> = self.sprites.iter().cloned().collect();
-            return v.into_iter();
+            self.sprites.clone().into_vec().into_iter()
         }
     }
 
@@ -0,0 +1,811 @@
+// Copyright 2018 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::Write;
+
+use crossterm::{cursor, event, queue, style, terminal};
+use log::debug;
+
+use crate::graphics::drawing::SpriteCell;
+use crate::graphics::render::RenderInterface;
+use crate::input::{ButtonState, Key, MouseButton};
+use crate::resources::color::Palette;
+use crate::{input, Driver, EngineSignal};
+
+/// Mapping from sprite index to Unicode for the standard IBM PC (code page 437) font layout. Most
+/// ASCII-style sprite sheets follow this layout.
+#[rustfmt::skip]
+const CP437: [char; 256] = [
+    ' ', '☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '○', '◙', '♂', '♀', '♪', '♫', '☼',
+    '►', '◄', '↕', '‼', '¶', '§', '▬', '↨', '↑', '↓', '→', '←', '∟', '↔', '▲', '▼',
+    ' ', '!', '"', '#', '$', '%', '&', '\'', '(', ')', '*', '+', ',', '-', '.', '/',
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', ':', ';', '<', '=', '>', '?',
+    '@', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O',
+    'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '[', '\\', ']', '^', '_',
+    '`', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o',
+    'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '{', '|', '}', '~', '⌂',
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+/// Convert a sprite index to the corresponding code page 437 character. Indices outside of the
+/// code page are rendered as `?`.
+pub fn cp437_to_char(sprite: u32) -> char {
+    CP437.get(sprite as usize).copied().unwrap_or('?')
+}
+
+/// Convert an RGB color to the closest entry in the xterm 256-color palette. Only the 6x6x6 color
+/// cube and the grayscale ramp are considered, since the first 16 colors are usually themed by the
+/// terminal.
+pub fn rgb_to_ansi256(c: [u8; 3]) -> u8 {
+    const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    fn nearest_level(v: u8) -> usize {
+        CUBE_LEVELS
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, l)| (**l as i32 - v as i32).abs())
+            .map(|(i, _)| i)
+            .unwrap()
+    }
+    fn distance(a: [u8; 3], b: [u8; 3]) -> i32 {
+        a.iter()
+            .zip(b.iter())
+            .map(|(x, y)| (*x as i32 - *y as i32).pow(2))
+            .sum()
+    }
+
+    let (r, g, b) = (
+        nearest_level(c[0]),
+        nearest_level(c[1]),
+        nearest_level(c[2]),
+    );
+    let cube_index = 16 + 36 * r + 6 * g + b;
+    let cube_color = [CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]];
+
+    // The grayscale ramp runs from 8 to 238 in steps of 10.
+    let average = (c[0] as i32 + c[1] as i32 + c[2] as i32) / 3;
+    let gray_step = ((average - 8 + 5) / 10).clamp(0, 23);
+    let gray_level = (8 + gray_step * 10) as u8;
+    let gray_index = 232 + gray_step as usize;
+
+    if distance(c, [gray_level; 3]) < distance(c, cube_color) {
+        gray_index as u8
+    } else {
+        cube_index as u8
+    }
+}
+
+/// How colors are emitted to the terminal.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorMode {
+    /// 24-bit color.
+    TrueColor,
+    /// The xterm 256-color palette.
+    Ansi256,
+}
+
+impl ColorMode {
+    /// Guess the color mode supported by the current terminal from the `COLORTERM` environment
+    /// variable.
+    pub fn detect() -> Self {
+        match std::env::var("COLORTERM") {
+            Ok(v) if v == "truecolor" || v == "24bit" => ColorMode::TrueColor,
+            _ => ColorMode::Ansi256,
+        }
+    }
+
+    fn convert(&self, c: [u8; 3]) -> style::Color {
+        match self {
+            ColorMode::TrueColor => style::Color::Rgb {
+                r: c[0],
+                g: c[1],
+                b: c[2],
+            },
+            ColorMode::Ansi256 => style::Color::AnsiValue(rgb_to_ansi256(c)),
+        }
+    }
+}
+
+/// A single character cell on the terminal.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct TerminalCell {
+    glyph: char,
+    foreground: [u8; 3],
+    background: [u8; 3],
+}
+
+/// A renderer that draws the sprite matrix as characters on a terminal. Each sprite cell becomes
+/// one terminal cell: the sprite index is mapped to a glyph with the code page 437 table, palette
+/// index 0 is used as the background color and palette index 1 as the foreground color.
+///
+/// `present()` only emits the cells that changed since the previous call.
+pub struct TerminalRenderer {
+    // Width & height, in cells.
+    width: u32,
+    height: u32,
+    color_mode: ColorMode,
+
+    // Layers are composited in order, so the last layer is on top.
+    layers: Vec<Box<[SpriteCell]>>,
+
+    // What is currently on the terminal; `None` if it is unknown and must be redrawn.
+    screen: Box<[Option<TerminalCell>]>,
+
+    last_render_time: time::OffsetDateTime,
+    fps: f32,
+}
+
+impl TerminalRenderer {
+    /// Create a new `TerminalRenderer` with the given width and height (measured in cells).
+    pub fn new(width: u32, height: u32, color_mode: ColorMode) -> Self {
+        TerminalRenderer {
+            width,
+            height,
+            color_mode,
+            layers: vec![Self::new_layer(width, height, true)],
+            screen: vec![None; (width * height) as usize].into_boxed_slice(),
+            last_render_time: time::OffsetDateTime::now_utc(),
+            fps: 0.0,
+        }
+    }
+
+    fn new_layer(width: u32, height: u32, opaque: bool) -> Box<[SpriteCell]> {
+        let cell = SpriteCell {
+            palette: Palette::mono([255, 255, 255]).set(0, [0, 0, 0]),
+            transparent: !opaque,
+            ..Default::default()
+        };
+        vec![cell; (width * height) as usize].into_boxed_slice()
+    }
+
+    /// Forget what is on the terminal, so the next call to `present()` redraws everything.
+    pub fn invalidate(&mut self) {
+        for c in self.screen.iter_mut() {
+            *c = None;
+        }
+    }
+
+    /// Composite the layers at cell `i` into a single terminal cell. The glyph and foreground come
+    /// from the topmost layer that isn't transparent there; if that layer keys out its background,
+    /// the background of the layers below is used instead.
+    fn composite(&self, i: usize) -> TerminalCell {
+        let mut result = TerminalCell {
+            glyph: ' ',
+            foreground: [0, 0, 0],
+            background: [0, 0, 0],
+        };
+        for layer in self.layers.iter() {
+            let cell = &layer[i];
+            if cell.transparent {
+                continue;
+            }
+            if !cell.is_transparent_at(1) {
+                result.glyph = cp437_to_char(cell.sprite);
+                result.foreground = cell.palette[1];
+            }
+            if !cell.is_transparent_at(0) {
+                result.background = cell.palette[0];
+            }
+        }
+        result
+    }
+
+    /// Write the changes since the last call to `out`.
+    pub fn present<W: Write>(&mut self, out: &mut W) -> std::io::Result<()> {
+        let mut cursor_position = None;
+        let mut foreground = None;
+        let mut background = None;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let i = (y * self.width + x) as usize;
+                let cell = self.composite(i);
+                if self.screen[i] == Some(cell) {
+                    continue;
+                }
+                if cursor_position != Some((x, y)) {
+                    queue!(out, cursor::MoveTo(x as u16, y as u16))?;
+                }
+                if foreground != Some(cell.foreground) {
+                    queue!(
+                        out,
+                        style::SetForegroundColor(self.color_mode.convert(cell.foreground))
+                    )?;
+                    foreground = Some(cell.foreground);
+                }
+                if background != Some(cell.background) {
+                    queue!(
+                        out,
+                        style::SetBackgroundColor(self.color_mode.convert(cell.background))
+                    )?;
+                    background = Some(cell.background);
+                }
+                queue!(out, style::Print(cell.glyph))?;
+                cursor_position = Some((x + 1, y));
+                self.screen[i] = Some(cell);
+            }
+        }
+        out.flush()?;
+
+        let t = time::OffsetDateTime::now_utc();
+        let dt_micros = (t - self.last_render_time).whole_microseconds();
+        if dt_micros > 0 {
+            let new_fps = 1_000_000.0 / dt_micros as f32;
+            self.fps = 0.9 * self.fps + 0.1 * new_fps;
+        }
+        self.last_render_time = t;
+
+        Ok(())
+    }
+}
+
+impl RenderInterface for TerminalRenderer {
+    fn update<'a, T, U>(&mut self, data: T)
+    where
+        T: Iterator<Item = U>,
+        U: Into<&'a SpriteCell>,
+    {
+        self.update_layer(0, data);
+    }
+
+    fn update_layer<'a, T, U>(&mut self, layer: usize, data: T)
+    where
+        T: Iterator<Item = U>,
+        U: Into<&'a SpriteCell>,
+    {
+        for (dst, src) in self.layers[layer].iter_mut().zip(data) {
+            *dst = *src.into();
+        }
+    }
+
+    fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    fn set_layer_count(&mut self, count: usize) {
+        let count = count.max(1);
+        self.layers.truncate(count);
+        while self.layers.len() < count {
+            self.layers
+                .push(Self::new_layer(self.width, self.height, false));
+        }
+    }
+
+    fn get_fps(&self) -> f32 {
+        self.fps
+    }
+
+    fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+}
+
+fn convert_modifiers(m: event::KeyModifiers) -> input::ModifiersState {
+    input::ModifiersState {
+        ctrl: m.contains(event::KeyModifiers::CONTROL),
+        alt: m.contains(event::KeyModifiers::ALT),
+        shift: m.contains(event::KeyModifiers::SHIFT),
+        meta: m.contains(event::KeyModifiers::SUPER),
+    }
+}
+
+fn convert_char(c: char) -> Option<Key> {
+    use Key::*;
+    #[rustfmt::skip]
+    const LETTERS: [Key; 26] = [
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    ];
+    const DIGITS: [Key; 10] = [Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9];
+    match c {
+        'a'..='z' => Some(LETTERS[c as usize - 'a' as usize]),
+        'A'..='Z' => Some(LETTERS[c as usize - 'A' as usize]),
+        '0'..='9' => Some(DIGITS[c as usize - '0' as usize]),
+        ' ' => Some(Space),
+        '\'' => Some(Apostrophe),
+        '*' => Some(Asterisk),
+        '@' => Some(At),
+        '\\' => Some(Backslash),
+        '^' => Some(Caret),
+        ':' => Some(Colon),
+        ',' => Some(Comma),
+        '=' => Some(Equals),
+        '`' => Some(Grave),
+        '[' => Some(LBracket),
+        ']' => Some(RBracket),
+        '-' => Some(Minus),
+        '.' => Some(Period),
+        '+' => Some(Plus),
+        ';' => Some(Semicolon),
+        '/' => Some(Slash),
+        _ => None,
+    }
+}
+
+fn convert_keycode(k: event::KeyCode) -> Option<Key> {
+    use Key::*;
+    const FUNCTION_KEYS: [Key; 24] = [
+        F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, F13, F14, F15, F16, F17, F18, F19, F20,
+        F21, F22, F23, F24,
+    ];
+    match k {
+        event::KeyCode::Backspace => Some(Back),
+        event::KeyCode::Enter => Some(Return),
+        event::KeyCode::Left => Some(Left),
+        event::KeyCode::Right => Some(Right),
+        event::KeyCode::Up => Some(Up),
+        event::KeyCode::Down => Some(Down),
+        event::KeyCode::Home => Some(Home),
+        event::KeyCode::End => Some(End),
+        event::KeyCode::PageUp => Some(PageUp),
+        event::KeyCode::PageDown => Some(PageDown),
+        event::KeyCode::Tab | event::KeyCode::BackTab => Some(Tab),
+        event::KeyCode::Delete => Some(Delete),
+        event::KeyCode::Insert => Some(Insert),
+        event::KeyCode::F(n) => FUNCTION_KEYS.get((n as usize).wrapping_sub(1)).copied(),
+        event::KeyCode::Char(c) => convert_char(c),
+        event::KeyCode::Esc => Some(Escape),
+        event::KeyCode::CapsLock => Some(Capital),
+        event::KeyCode::ScrollLock => Some(Scroll),
+        event::KeyCode::NumLock => Some(Numlock),
+        event::KeyCode::PrintScreen => Some(Snapshot),
+        event::KeyCode::Pause => Some(Pause),
+        _ => None,
+    }
+}
+
+fn convert_mouse_button(b: event::MouseButton) -> MouseButton {
+    match b {
+        event::MouseButton::Left => MouseButton::Left,
+        event::MouseButton::Right => MouseButton::Right,
+        event::MouseButton::Middle => MouseButton::Middle,
+    }
+}
+
+/// Convert a terminal input event into the equivalent `dashing` events. Terminal cells map
+/// one-to-one to sprite cells, so mouse positions are reported directly as sprite positions.
+pub fn convert_event(e: event::Event) -> Vec<input::Event> {
+    match e {
+        event::Event::FocusGained => vec![input::Event::Window(input::WindowEvent::Focused(true))],
+        event::Event::FocusLost => vec![input::Event::Window(input::WindowEvent::Focused(false))],
+        event::Event::Key(k) => {
+            let mut modifiers = convert_modifiers(k.modifiers);
+            if k.code == event::KeyCode::BackTab {
+                modifiers.shift = true;
+            }
+            let state = match k.kind {
+                event::KeyEventKind::Press | event::KeyEventKind::Repeat => ButtonState::Pressed,
+                event::KeyEventKind::Release => ButtonState::Released,
+            };
+            let mut events = vec![input::Event::Keyboard(input::KeyboardEvent::KeyPress {
+                scancode: 0,
+                state,
+                virtual_keycode: convert_keycode(k.code),
+                modifiers,
+            })];
+            if let (event::KeyCode::Char(c), ButtonState::Pressed) = (k.code, state) {
+                events.push(input::Event::Keyboard(input::KeyboardEvent::Character(c)));
+            }
+            events
+        }
+        event::Event::Mouse(m) => {
+            let modifiers = convert_modifiers(m.modifiers);
+            let cell = (m.column as u32, m.row as u32);
+            let moved = input::Event::Mouse(input::MouseEvent::CursorMoved {
+                sprite_position: cell,
+                absolute_position: (m.column as f64, m.row as f64),
+            });
+            match m.kind {
+                event::MouseEventKind::Moved | event::MouseEventKind::Drag(_) => vec![moved],
+                event::MouseEventKind::Down(b) => vec![
+                    moved,
+                    input::Event::Mouse(input::MouseEvent::Button {
+                        state: ButtonState::Pressed,
+                        button: convert_mouse_button(b),
+                        cell: Some(cell),
+                        modifiers,
+                    }),
+                ],
+                event::MouseEventKind::Up(b) => vec![
+                    moved,
+                    input::Event::Mouse(input::MouseEvent::Button {
+                        state: ButtonState::Released,
+                        button: convert_mouse_button(b),
+                        cell: Some(cell),
+                        modifiers,
+                    }),
+                ],
+                event::MouseEventKind::ScrollDown => vec![
+                    moved,
+                    input::Event::Mouse(input::MouseEvent::Wheel {
+                        delta: input::ScrollDelta::Lines(0.0, -1.0),
+                        cell: Some(cell),
+                        modifiers,
+                    }),
+                ],
+                event::MouseEventKind::ScrollUp => vec![
+                    moved,
+                    input::Event::Mouse(input::MouseEvent::Wheel {
+                        delta: input::ScrollDelta::Lines(0.0, 1.0),
+                        cell: Some(cell),
+                        modifiers,
+                    }),
+                ],
+            }
+        }
+        _ => vec![],
+    }
+}
+
+/// Restores the terminal to its normal state when dropped, so that panics and early returns don't
+/// leave it in raw mode.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn new<W: Write>(out: &mut W) -> std::io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let guard = TerminalGuard;
+        crossterm::execute!(
+            out,
+            terminal::EnterAlternateScreen,
+            terminal::Clear(terminal::ClearType::All),
+            cursor::Hide,
+            event::EnableMouseCapture,
+            event::EnableFocusChange
+        )?;
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = crossterm::execute!(
+            std::io::stdout(),
+            event::DisableFocusChange,
+            event::DisableMouseCapture,
+            style::ResetColor,
+            cursor::Show,
+            terminal::LeaveAlternateScreen
+        );
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// `TerminalEngine` is the terminal equivalent of `Engine`: it runs a `Driver` in the current
+/// terminal instead of a window, so the same game can be played over SSH.
+pub struct TerminalEngine<D>
+where
+    D: Driver,
+{
+    driver: D,
+    renderer: TerminalRenderer,
+    frame_time: std::time::Duration,
+    mouse_tracker: input::MouseTracker,
+    timestep: crate::timestep::FixedTimestep,
+}
+
+impl<D> TerminalEngine<D>
+where
+    D: Driver,
+{
+    /// Create a new `TerminalEngine` with the given width and height (measured in cells). The
+    /// color mode is detected from the environment.
+    pub fn new(width: u32, height: u32, driver: D) -> Self {
+        TerminalEngine {
+            driver,
+            renderer: TerminalRenderer::new(width, height, ColorMode::detect()),
+            frame_time: std::time::Duration::from_secs(1) / 60,
+            mouse_tracker: Default::default(),
+            timestep: crate::timestep::FixedTimestep::new(
+                crate::DEFAULT_TICK_RATE,
+                crate::DEFAULT_MAX_CATCH_UP_STEPS,
+            ),
+        }
+    }
+
+    /// Override the detected color mode.
+    #[must_use]
+    pub fn with_color_mode(mut self, color_mode: ColorMode) -> Self {
+        self.renderer.color_mode = color_mode;
+
+        self
+    }
+
+    /// Set the maximum number of frames drawn per second. Defaults to 60.
+    #[must_use]
+    pub fn with_frame_rate(mut self, fps: u32) -> Self {
+        self.frame_time = std::time::Duration::from_secs(1) / fps.max(1);
+
+        self
+    }
+
+    /// Set the number of times per second `Driver::update()` is called. Defaults to 60.
+    #[must_use]
+    pub fn with_tick_rate(mut self, ticks_per_second: u32) -> Self {
+        self.timestep.set_rate(ticks_per_second);
+
+        self
+    }
+
+    /// Set the maximum number of `Driver::update()` ticks run before a single frame is drawn.
+    /// Defaults to 5.
+    #[must_use]
+    pub fn with_max_catch_up_steps(mut self, steps: u32) -> Self {
+        self.timestep.set_max_steps(steps);
+
+        self
+    }
+
+    /// Run the main loop until one of the library hooks tells us to quit, then return the driver.
+    /// `Driver::on_exit()` is called before this returns, even if the loop failed with an I/O
+    /// error.
+    pub fn run(mut self) -> std::io::Result<D> {
+        let mut stdout = std::io::stdout();
+        let result = TerminalGuard::new(&mut stdout).and_then(|_guard| self.run_loop(&mut stdout));
+        self.driver.on_exit();
+        result.map(|()| self.driver)
+    }
+
+    fn run_loop(&mut self, stdout: &mut std::io::Stdout) -> std::io::Result<()> {
+        self.driver.on_start(&mut self.renderer);
+        loop {
+            let frame_start = std::time::Instant::now();
+
+            // Process all pending input, waiting at most until the next frame is due.
+            let mut timeout = self.frame_time;
+            while event::poll(timeout)? {
+                let e = event::read()?;
+                match e {
+                    event::Event::Resize(columns, rows) => {
+                        // The terminal may have reflowed its contents, so redraw everything.
+                        queue!(stdout, terminal::Clear(terminal::ClearType::All))?;
+                        self.renderer.invalidate();
+                        // Terminals have no pixels to speak of, so report the size in characters.
+                        self.driver.on_resize(
+                            (columns as u32, rows as u32),
+                            (self.renderer.width, self.renderer.height),
+                        );
+                    }
+                    event::Event::FocusGained => self.driver.on_focus_changed(true),
+                    event::Event::FocusLost => self.driver.on_focus_changed(false),
+                    _ => {}
+                }
+                let now = std::time::Instant::now();
+                let (width, height) = (self.renderer.width, self.renderer.height);
+                for e in convert_event(e)
+                    .into_iter()
+                    .map(|e| match e {
+                        // The terminal may be larger than the grid.
+                        input::Event::Mouse(input::MouseEvent::CursorMoved {
+                            sprite_position: (x, y),
+                            ..
+                        }) if x >= width || y >= height => {
+                            input::Event::Mouse(input::MouseEvent::CursorLeftGrid)
+                        }
+                        e => e,
+                    })
+                    .flat_map(|e| self.mouse_tracker.process(e, now))
+                {
+                    debug!("{:?}", e);
+                    if self.driver.handle_input(e) == EngineSignal::Halt {
+                        return Ok(());
+                    }
+                }
+                timeout = self.frame_time.saturating_sub(frame_start.elapsed());
+            }
+
+            if self.advance_frame(std::time::Instant::now()) == EngineSignal::Halt {
+                return Ok(());
+            }
+            self.renderer.present(stdout)?;
+        }
+    }
+
+    /// Run the `Driver::update()` ticks due at `now`, then let the driver draw the frame. Once
+    /// the driver asks to halt, no more ticks are run and the frame isn't drawn.
+    fn advance_frame(&mut self, now: std::time::Instant) -> EngineSignal {
+        let mut signal = EngineSignal::Continue;
+        for _ in 0..self.timestep.advance(now) {
+            signal.update(self.driver.update(self.timestep.tick()));
+            if signal == EngineSignal::Halt {
+                return signal;
+            }
+        }
+        signal.update(
+            self.driver
+                .process_frame_interpolated(&mut self.renderer, self.timestep.alpha()),
+        );
+        signal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cp437() {
+        assert_eq!(cp437_to_char('A' as u32), 'A');
+        assert_eq!(cp437_to_char(1), '☺');
+        assert_eq!(cp437_to_char(0xDB), '█');
+        assert_eq!(cp437_to_char(1000), '?');
+    }
+
+    #[test]
+    fn ansi256() {
+        assert_eq!(rgb_to_ansi256([0, 0, 0]), 16);
+        assert_eq!(rgb_to_ansi256([255, 255, 255]), 231);
+        assert_eq!(rgb_to_ansi256([255, 0, 0]), 196);
+        assert_eq!(rgb_to_ansi256([128, 128, 128]), 244);
+    }
+
+    #[test]
+    fn present_only_changed_cells() {
+        let mut renderer = TerminalRenderer::new(3, 2, ColorMode::TrueColor);
+        let mut layer = crate::graphics::drawing::SpriteLayer::new(3, 2);
+        for c in layer.iter_mut() {
+            c.sprite = 'a' as u32;
+        }
+        renderer.update(layer.iter());
+
+        let mut out = Vec::new();
+        renderer.present(&mut out).unwrap();
+        let first = String::from_utf8(out).unwrap();
+        assert_eq!(first.matches('a').count(), 6);
+
+        // Nothing changed, so nothing should be written.
+        let mut out = Vec::new();
+        renderer.present(&mut out).unwrap();
+        assert!(out.is_empty());
+
+        layer[(1, 1)].sprite = 'b' as u32;
+        renderer.update(layer.iter());
+        let mut out = Vec::new();
+        renderer.present(&mut out).unwrap();
+        let third = String::from_utf8(out).unwrap();
+        assert!(!third.contains('a'));
+        assert_eq!(third.matches('b').count(), 1);
+        // Cursor moves are 1-based: row 2, column 2.
+        assert!(third.starts_with("\u{1b}[2;2H"));
+    }
+
+    #[test]
+    fn composite_keyed_background() {
+        let mut renderer = TerminalRenderer::new(1, 1, ColorMode::TrueColor);
+        renderer.set_layer_count(2);
+        renderer.update_layer(
+            0,
+            [SpriteCell {
+                palette: Palette::mono([0, 0, 255]),
+                sprite: '.' as u32,
+                ..Default::default()
+            }]
+            .iter(),
+        );
+        renderer.update_layer(
+            1,
+            [SpriteCell {
+                palette: Palette::mono([255, 0, 0]),
+                sprite: '@' as u32,
+                ..Default::default()
+            }
+            .with_transparent_index(0)]
+            .iter(),
+        );
+        assert_eq!(
+            renderer.composite(0),
+            TerminalCell {
+                glyph: '@',
+                foreground: [255, 0, 0],
+                background: [0, 0, 255],
+            }
+        );
+    }
+
+    #[test]
+    fn convert_key() {
+        let events = convert_event(event::Event::Key(event::KeyEvent::new(
+            event::KeyCode::Char('q'),
+            event::KeyModifiers::CONTROL,
+        )));
+        assert_eq!(
+            events,
+            vec![
+                input::Event::Keyboard(input::KeyboardEvent::KeyPress {
+                    scancode: 0,
+                    state: ButtonState::Pressed,
+                    virtual_keycode: Some(Key::Q),
+                    modifiers: input::ModifiersState {
+                        ctrl: true,
+                        ..Default::default()
+                    },
+                }),
+                input::Event::Keyboard(input::KeyboardEvent::Character('q')),
+            ]
+        );
+    }
+
+    #[test]
+    fn convert_mouse() {
+        let events = convert_event(event::Event::Mouse(event::MouseEvent {
+            kind: event::MouseEventKind::Down(event::MouseButton::Left),
+            column: 4,
+            row: 2,
+            modifiers: event::KeyModifiers::NONE,
+        }));
+        assert_eq!(
+            events,
+            vec![
+                input::Event::Mouse(input::MouseEvent::CursorMoved {
+                    sprite_position: (4, 2),
+                    absolute_position: (4.0, 2.0),
+                }),
+                input::Event::Mouse(input::MouseEvent::Button {
+                    state: ButtonState::Pressed,
+                    button: MouseButton::Left,
+                    cell: Some((4, 2)),
+                    modifiers: Default::default(),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn halt_on_update() {
+        /// Halts on its second update.
+        #[derive(Default)]
+        struct HaltingDriver {
+            updates: usize,
+            frames: usize,
+        }
+
+        impl Driver for HaltingDriver {
+            fn handle_input(&mut self, _event: input::Event) -> EngineSignal {
+                EngineSignal::Continue
+            }
+
+            fn update(&mut self, _dt: std::time::Duration) -> EngineSignal {
+                self.updates += 1;
+                if self.updates == 2 {
+                    EngineSignal::Halt
+                } else {
+                    EngineSignal::Continue
+                }
+            }
+
+            fn process_frame<R>(&mut self, _renderer: &mut R) -> EngineSignal
+            where
+                R: RenderInterface,
+            {
+                self.frames += 1;
+                EngineSignal::Continue
+            }
+        }
+
+        let mut engine = TerminalEngine::new(1, 1, HaltingDriver::default())
+            .with_tick_rate(10)
+            .with_max_catch_up_steps(5);
+        let start = std::time::Instant::now();
+        assert_eq!(engine.advance_frame(start), EngineSignal::Continue);
+        assert_eq!((engine.driver.updates, engine.driver.frames), (0, 1));
+        // Three ticks are due, but the loop stops at the halt and the frame isn't drawn.
+        let later = start + std::time::Duration::from_millis(300);
+        assert_eq!(engine.advance_frame(later), EngineSignal::Halt);
+        assert_eq!((engine.driver.updates, engine.driver.frames), (2, 1));
+    }
+}
@@ -0,0 +1,394 @@
+// Copyright 2018 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::graphics::animation::{Animation, AnimationId, PaletteCycle, PaletteCycleId};
+use crate::graphics::drawing::SpriteCell;
+use crate::graphics::render::{
+    Camera, FrameStats, OffscreenRenderer, RenderError, RenderInterface,
+};
+use crate::resources::sprite::SpriteTexture;
+use crate::{input, Driver, EngineSignal};
+
+/// Wraps a renderer, keeping a copy of the cells most recently uploaded to each layer so that
+/// tests can inspect them without reading back pixels.
+struct RecordingRenderer<R> {
+    inner: R,
+    layers: Vec<Vec<SpriteCell>>,
+}
+
+impl<R: RenderInterface> RenderInterface for RecordingRenderer<R> {
+    fn update<'a, T, U>(&mut self, data: T)
+    where
+        T: Iterator<Item = U>,
+        U: Into<&'a SpriteCell>,
+    {
+        self.update_layer(0, data);
+    }
+
+    fn update_layer<'a, T, U>(&mut self, layer: usize, data: T)
+    where
+        T: Iterator<Item = U>,
+        U: Into<&'a SpriteCell>,
+    {
+        let cells: Vec<SpriteCell> = data.map(|c| *c.into()).collect();
+        self.inner.update_layer(layer, cells.iter());
+        self.layers[layer] = cells;
+    }
+
+    fn layer_count(&self) -> usize {
+        self.inner.layer_count()
+    }
+
+    fn set_layer_count(&mut self, count: usize) {
+        self.inner.set_layer_count(count);
+        self.layers.resize(self.inner.layer_count(), vec![]);
+    }
+
+    fn get_fps(&self) -> f32 {
+        self.inner.get_fps()
+    }
+
+    fn dimensions(&self) -> (u32, u32) {
+        self.inner.dimensions()
+    }
+
+    fn set_camera(&mut self, camera: Camera, transition: std::time::Duration) {
+        self.inner.set_camera(camera, transition);
+    }
+
+    fn camera(&self) -> Camera {
+        self.inner.camera()
+    }
+
+    fn set_post_process_uniforms<T: bytemuck::Pod>(&mut self, pass: usize, uniforms: T) {
+        self.inner.set_post_process_uniforms(pass, uniforms);
+    }
+
+    fn set_post_process_enabled(&mut self, pass: usize, enabled: bool) {
+        self.inner.set_post_process_enabled(pass, enabled);
+    }
+
+    fn set_sprite_texture(&mut self, sprite_texture: &SpriteTexture) {
+        self.inner.set_sprite_texture(sprite_texture);
+    }
+
+    fn add_animation(&mut self, animation: &Animation) -> AnimationId {
+        self.inner.add_animation(animation)
+    }
+
+    fn add_palette_cycle(&mut self, cycle: &PaletteCycle) -> PaletteCycleId {
+        self.inner.add_palette_cycle(cycle)
+    }
+
+    fn frame_stats(&self) -> Option<&FrameStats> {
+        self.inner.frame_stats()
+    }
+
+    fn set_stats_overlay(&mut self, visible: bool) {
+        self.inner.set_stats_overlay(visible);
+    }
+
+    fn set_gpu_timing(&mut self, enabled: bool) {
+        self.inner.set_gpu_timing(enabled);
+    }
+}
+
+/// `TestHarness` runs a `Driver` the same way `Engine` does, but against an offscreen renderer and
+/// a scripted sequence of input events instead of a window. This makes it possible to write
+/// integration tests for game code.
+///
+/// Each frame calls `Driver::update()` exactly once with a fixed `dt`, followed by
+/// `Driver::process_frame_interpolated()` with an alpha of zero, so runs are deterministic.
+pub struct TestHarness<D, R> {
+    driver: D,
+    renderer: RecordingRenderer<R>,
+    mouse_tracker: input::MouseTracker,
+    tick: std::time::Duration,
+    capture_frames: bool,
+    frames: Vec<Box<[u8]>>,
+    frame_count: usize,
+    started: bool,
+}
+
+impl<D, R> TestHarness<D, R>
+where
+    D: Driver,
+    R: OffscreenRenderer,
+{
+    /// Create a new harness for `driver`, rendering with `renderer`.
+    pub fn new(driver: D, renderer: R) -> Self {
+        let layer_count = renderer.layer_count();
+        TestHarness {
+            driver,
+            renderer: RecordingRenderer {
+                inner: renderer,
+                layers: vec![vec![]; layer_count],
+            },
+            mouse_tracker: Default::default(),
+            tick: std::time::Duration::from_secs(1) / crate::DEFAULT_TICK_RATE,
+            capture_frames: false,
+            frames: vec![],
+            frame_count: 0,
+            started: false,
+        }
+    }
+
+    /// Set the `dt` passed to `Driver::update()`. Defaults to 1/60th of a second.
+    #[must_use]
+    pub fn with_tick(mut self, dt: std::time::Duration) -> Self {
+        self.tick = dt;
+
+        self
+    }
+
+    /// Render and keep the pixels of every frame, so they can be checked with `frames()`. This is
+    /// off by default, since rendering is comparatively slow.
+    #[must_use]
+    pub fn with_frame_capture(mut self) -> Self {
+        self.capture_frames = true;
+
+        self
+    }
+
+    /// Call `Driver::on_start()`, unless it has been called already.
+    fn ensure_started(&mut self) {
+        if !self.started {
+            self.started = true;
+            self.driver.on_start(&mut self.renderer);
+        }
+    }
+
+    /// Deliver input events to the driver, in order. `Driver::on_start()` is called first if no
+    /// frames have been run yet. Mouse events are annotated and synthesized (e.g. drags and cell
+    /// enter/leave events) just as they are by `Engine`. Stops early and returns
+    /// `EngineSignal::Halt` if the driver asks to halt.
+    pub fn send_events<I>(&mut self, events: I) -> EngineSignal
+    where
+        I: IntoIterator<Item = input::Event>,
+    {
+        self.ensure_started();
+        let now = std::time::Instant::now();
+        for e in events {
+            for e in self.mouse_tracker.process(e, now) {
+                if self.driver.handle_input(e) == EngineSignal::Halt {
+                    return EngineSignal::Halt;
+                }
+            }
+        }
+        EngineSignal::Continue
+    }
+
+    /// Run `count` frames. `Driver::on_start()` is called before the first frame, unless events
+    /// have already been sent. Stops early and returns `EngineSignal::Halt` if the driver asks to
+    /// halt; if it does so from `Driver::update()`, that frame isn't drawn or counted.
+    pub fn run_frames(&mut self, count: usize) -> Result<EngineSignal, RenderError> {
+        self.ensure_started();
+        for _ in 0..count {
+            if self.driver.update(self.tick) == EngineSignal::Halt {
+                return Ok(EngineSignal::Halt);
+            }
+            let mut signal = EngineSignal::Continue;
+            signal.update(
+                self.driver
+                    .process_frame_interpolated(&mut self.renderer, 0.0),
+            );
+            self.frame_count += 1;
+            if self.capture_frames {
+                self.frames.push(self.renderer.inner.capture_frame()?);
+            }
+            if signal == EngineSignal::Halt {
+                return Ok(EngineSignal::Halt);
+            }
+        }
+        Ok(EngineSignal::Continue)
+    }
+
+    /// Simulate a window resize that changes the grid to `width` x `height` cells, as happens
+    /// with `WindowBuilder::with_resizable_grid()`. The renderer is resized and
+    /// `Driver::on_resize()` is called.
+    pub fn resize_grid(&mut self, width: u32, height: u32) {
+        self.renderer.inner.resize_grid(width, height);
+        for layer in self.renderer.layers.iter_mut() {
+            layer.clear();
+        }
+        let cells = self.renderer.inner.dimensions();
+        self.driver
+            .on_resize(self.renderer.inner.output_size(), cells);
+    }
+
+    /// Render the current state of the renderer, independently of frame capture.
+    pub fn capture_frame(&mut self) -> Result<Box<[u8]>, RenderError> {
+        self.renderer.inner.capture_frame()
+    }
+
+    /// Frames captured so far, if frame capture is enabled. See `with_frame_capture()`.
+    pub fn frames(&self) -> &[Box<[u8]>] {
+        &self.frames
+    }
+
+    /// Number of frames run so far.
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    /// The cells most recently sent to the given layer, or an empty slice if the driver hasn't
+    /// drawn to it yet. Panics if `layer` is not less than the renderer's layer count.
+    pub fn layer(&self, layer: usize) -> &[SpriteCell] {
+        &self.renderer.layers[layer]
+    }
+
+    /// Get the driver.
+    pub fn driver(&self) -> &D {
+        &self.driver
+    }
+
+    /// Get the driver mutably, e.g. to set up a scenario.
+    pub fn driver_mut(&mut self) -> &mut D {
+        &mut self.driver
+    }
+
+    /// Get the renderer.
+    pub fn renderer(&self) -> &R {
+        &self.renderer.inner
+    }
+
+    /// Consume the harness, returning the driver. `Driver::on_exit()` is called first, as it is
+    /// when the engine's main loop ends.
+    pub fn into_driver(mut self) -> D {
+        self.driver.on_exit();
+        self.driver
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::drawing::SpriteLayer;
+    use crate::graphics::render::SoftwareRenderer;
+    use crate::resources::sprite::SpriteTexture;
+
+    /// Draws sprite 0 until a key is pressed, then sprite 1. Halts after a click, or on the next
+    /// update once `halt_on_update` is set.
+    struct ToggleDriver {
+        layer: SpriteLayer,
+        updates: usize,
+        halt_on_update: bool,
+        exited: bool,
+    }
+
+    impl Driver for ToggleDriver {
+        fn handle_input(&mut self, event: input::Event) -> EngineSignal {
+            match event {
+                input::Event::Keyboard(_) => self.layer[0].sprite = 1,
+                input::Event::Mouse(input::MouseEvent::Button { .. }) => return EngineSignal::Halt,
+                _ => {}
+            }
+            EngineSignal::Continue
+        }
+
+        fn on_start<R>(&mut self, renderer: &mut R)
+        where
+            R: RenderInterface,
+        {
+            renderer.set_layer_count(2);
+        }
+
+        fn on_exit(&mut self) {
+            self.exited = true;
+        }
+
+        fn update(&mut self, _dt: std::time::Duration) -> EngineSignal {
+            self.updates += 1;
+            if self.halt_on_update {
+                EngineSignal::Halt
+            } else {
+                EngineSignal::Continue
+            }
+        }
+
+        fn process_frame<R>(&mut self, renderer: &mut R) -> EngineSignal
+        where
+            R: RenderInterface,
+        {
+            renderer.update(self.layer.iter());
+            EngineSignal::Continue
+        }
+    }
+
+    fn harness() -> TestHarness<ToggleDriver, SoftwareRenderer> {
+        // Two 1x1 sprites, with palette indices 0 and 1.
+        let tex = SpriteTexture::new_from_pixels(&[0, 1], 2, 1, 1, 1, 2).unwrap();
+        TestHarness::new(
+            ToggleDriver {
+                layer: SpriteLayer::new(1, 1),
+                updates: 0,
+                halt_on_update: false,
+                exited: false,
+            },
+            SoftwareRenderer::new(1, 1, &tex),
+        )
+        .with_frame_capture()
+    }
+
+    #[test]
+    fn run_frames() {
+        let mut h = harness();
+        assert!(h.layer(0).is_empty());
+        assert_eq!(h.run_frames(2).unwrap(), EngineSignal::Continue);
+        assert_eq!(h.renderer().layer_count(), 2);
+        assert!(h.layer(1).is_empty());
+        assert_eq!(h.layer(0)[0].sprite, 0);
+
+        h.send_events([input::Event::Keyboard(input::KeyboardEvent::Character('x'))]);
+        assert_eq!(h.run_frames(1).unwrap(), EngineSignal::Continue);
+        assert_eq!(h.layer(0)[0].sprite, 1);
+
+        assert_eq!(h.frame_count(), 3);
+        assert_eq!(h.driver().updates, 3);
+        // Palette index 0 is black and index 1 is blue in the default palette.
+        assert_eq!(
+            h.frames().iter().map(|f| &f[..]).collect::<Vec<_>>(),
+            vec![&[0, 0, 0, 255], &[0, 0, 0, 255], &[0, 0, 0xaa, 255]]
+        );
+    }
+
+    #[test]
+    fn halt() {
+        let mut h = harness();
+        let click = input::Event::Mouse(input::MouseEvent::Button {
+            state: input::ButtonState::Pressed,
+            button: input::MouseButton::Left,
+            cell: None,
+            modifiers: Default::default(),
+        });
+        assert_eq!(h.send_events([click]), EngineSignal::Halt);
+        assert_eq!(h.renderer().layer_count(), 2);
+        assert!(!h.driver().exited);
+        assert!(h.into_driver().exited);
+    }
+
+    #[test]
+    fn halt_on_update() {
+        let mut h = harness();
+        assert_eq!(h.run_frames(1).unwrap(), EngineSignal::Continue);
+        h.driver_mut().halt_on_update = true;
+        h.driver_mut().layer[0].sprite = 1;
+        assert_eq!(h.run_frames(2).unwrap(), EngineSignal::Halt);
+        // The frame after the halt was never drawn.
+        assert_eq!(h.driver().updates, 2);
+        assert_eq!(h.frame_count(), 1);
+        assert_eq!(h.frames().len(), 1);
+        assert_eq!(h.layer(0)[0].sprite, 0);
+    }
+}
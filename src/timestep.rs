@@ -0,0 +1,106 @@
+// Copyright 2018 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::{Duration, Instant};
+
+/// Accumulates real time between frames and converts it into a whole number of fixed-length
+/// simulation ticks, so that game logic runs at the same speed regardless of the frame rate.
+#[derive(Debug)]
+pub(crate) struct FixedTimestep {
+    tick: Duration,
+    max_steps: u32,
+    accumulator: Duration,
+    last: Option<Instant>,
+}
+
+impl FixedTimestep {
+    /// Create a new timestep with `rate` ticks per second, running at most `max_steps` ticks per
+    /// frame.
+    pub(crate) fn new(rate: u32, max_steps: u32) -> Self {
+        FixedTimestep {
+            tick: Duration::from_secs(1) / rate.max(1),
+            max_steps,
+            accumulator: Duration::ZERO,
+            last: None,
+        }
+    }
+
+    /// Length of a single tick.
+    pub(crate) fn tick(&self) -> Duration {
+        self.tick
+    }
+
+    /// Set the number of ticks per second.
+    pub(crate) fn set_rate(&mut self, rate: u32) {
+        self.tick = Duration::from_secs(1) / rate.max(1);
+    }
+
+    /// Set the maximum number of ticks run per frame.
+    pub(crate) fn set_max_steps(&mut self, max_steps: u32) {
+        self.max_steps = max_steps;
+    }
+
+    /// Account for the time elapsed since the previous call, and return the number of ticks to
+    /// run this frame. If the simulation has fallen more than `max_steps` ticks behind (e.g.
+    /// because the window was being dragged), the excess time is dropped rather than run in a
+    /// burst later.
+    pub(crate) fn advance(&mut self, now: Instant) -> u32 {
+        if let Some(last) = self.last.replace(now) {
+            self.accumulator += now.saturating_duration_since(last);
+        }
+        let mut steps = 0;
+        while self.accumulator >= self.tick {
+            if steps == self.max_steps {
+                self.accumulator = Duration::from_nanos(
+                    (self.accumulator.as_nanos() % self.tick.as_nanos()) as u64,
+                );
+                break;
+            }
+            self.accumulator -= self.tick;
+            steps += 1;
+        }
+        steps
+    }
+
+    /// How far the current frame is between the last tick and the next one, in `[0, 1)`.
+    pub(crate) fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.tick.as_secs_f32()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_and_alpha() {
+        let mut t = FixedTimestep::new(10, 5);
+        let start = Instant::now();
+        assert_eq!(t.advance(start), 0);
+        assert_eq!(t.advance(start + Duration::from_millis(50)), 0);
+        assert!((t.alpha() - 0.5).abs() < 1e-6);
+        assert_eq!(t.advance(start + Duration::from_millis(250)), 2);
+        assert!((t.alpha() - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn catch_up_is_limited() {
+        let mut t = FixedTimestep::new(10, 3);
+        let start = Instant::now();
+        t.advance(start);
+        assert_eq!(t.advance(start + Duration::from_millis(1050)), 3);
+        assert!((t.alpha() - 0.5).abs() < 1e-6);
+        assert_eq!(t.advance(start + Duration::from_millis(1100)), 1);
+    }
+}
@@ -61,6 +61,25 @@ impl From<FilterMethod> for wgpu::FilterMode {
     }
 }
 
+/// How the rendered grid is scaled to fit the window when their sizes don't match. Areas of the
+/// window not covered by the grid are filled with the clear color.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ScalingMode {
+    /// Scale the grid as large as possible while preserving its aspect ratio, letterboxing the
+    /// rest of the window.
+    #[default]
+    Fit,
+    /// Like `Fit`, but only scale by whole multiples, so every sprite pixel covers the same number
+    /// of screen pixels. This pairs well with `FilterMethod::NearestNeighbor`. If the window is
+    /// smaller than the grid, this behaves like `Fit`.
+    IntegerFit,
+    /// Stretch the grid to cover the whole window, ignoring its aspect ratio.
+    Stretch,
+    /// Scale the grid to cover the whole window while preserving its aspect ratio, cropping
+    /// whatever doesn't fit.
+    Fill,
+}
+
 /// Helper for constructing windows.
 pub struct WindowBuilder<'a> {
     window_title: &'a str,
@@ -69,9 +88,14 @@ pub struct WindowBuilder<'a> {
     sprite_texture: &'a SpriteTexture,
     vsync: bool,
     resizable: bool,
+    resizable_grid: bool,
     full_screen: bool,
     clear_color: Color,
     filter_method: FilterMethod,
+    scaling_mode: ScalingMode,
+    cell_shader: Option<&'a str>,
+    screen_shader: Option<&'a str>,
+    post_process: Vec<render::PostProcessPass>,
 }
 
 impl<'a> WindowBuilder<'a> {
@@ -84,6 +108,7 @@ impl<'a> WindowBuilder<'a> {
     ///   - Not full screen
     ///   - Clear color 100% green
     ///   - Trilinear filtering
+    ///   - `ScalingMode::Fit`
     pub fn new(
         window_title: &'a str,
         width: u32,
@@ -97,9 +122,14 @@ impl<'a> WindowBuilder<'a> {
             sprite_texture,
             vsync: true,
             resizable: false,
+            resizable_grid: false,
             full_screen: false,
             clear_color: [0, 255, 0].into(),
             filter_method: FilterMethod::NearestNeighbor,
+            scaling_mode: ScalingMode::Fit,
+            cell_shader: None,
+            screen_shader: None,
+            post_process: vec![],
         }
     }
 
@@ -119,6 +149,20 @@ impl<'a> WindowBuilder<'a> {
         self
     }
 
+    /// Enable/disable grid resizing. When enabled, resizing the window changes the number of
+    /// columns and rows in the sprite grid to fill it (instead of scaling the grid and
+    /// letterboxing it), and the driver is told about the new size through
+    /// `Driver::on_resize()`. This also makes the window resizable.
+    #[must_use]
+    pub fn with_resizable_grid(mut self, enable: bool) -> Self {
+        self.resizable_grid = enable;
+        if enable {
+            self.resizable = true;
+        }
+
+        self
+    }
+
     /// Enable full screen mode.
     #[must_use]
     pub fn enable_full_screen(mut self) -> Self {
@@ -144,6 +188,66 @@ impl<'a> WindowBuilder<'a> {
         self
     }
 
+    /// Set how the grid is scaled to fit the window. Defaults to `ScalingMode::Fit`.
+    #[must_use]
+    pub fn with_scaling_mode(mut self, mode: ScalingMode) -> Self {
+        self.scaling_mode = mode;
+
+        self
+    }
+
+    /// Replace the shader that colors the pixels of each sprite cell, e.g. to make water shimmer
+    /// or torches flicker without redrawing those cells every frame. `source` is WGSL defining
+    ///
+    /// ```text
+    /// fn shade_cell(cell: Cell) -> vec4<f32>
+    /// ```
+    ///
+    /// which returns the color of one pixel. `Cell` holds the cell's coordinates, sprite index and
+    /// `SpriteCell::user_data`, the position within the cell, and the palette index of the sprite
+    /// pixel. The shader can also call `palette_color(cell, index)` to look up a color in the
+    /// cell's palette, `sprite_pixel(sprite, pixel)` to read another sprite, and use
+    /// `cell_globals.elapsed_time` and `cell_globals.frame_counter` for animation. See
+    /// `shader/cell_common.wgsl` for the details, and `shader/cell.wgsl` for the default shader.
+    ///
+    /// Palette indices keyed out with `SpriteCell::transparent_mask` stay transparent regardless
+    /// of the color returned. The shader is compiled when the window is built, and `build()`
+    /// returns an error if it is invalid.
+    #[must_use]
+    pub fn with_cell_shader(mut self, source: &'a str) -> Self {
+        self.cell_shader = Some(source);
+
+        self
+    }
+
+    /// Replace the shader that draws the sprite grid into the window. `source` is WGSL for a
+    /// fragment shader with the entry point `fs_main`, which has access to the same declarations
+    /// as a `PostProcessPass`; `screen_texture` is the unscaled sprite grid. The shader is
+    /// compiled when the window is built, and `build()` returns an error if it is invalid.
+    #[must_use]
+    pub fn with_screen_shader(mut self, source: &'a str) -> Self {
+        self.screen_shader = Some(source);
+
+        self
+    }
+
+    /// Add a post-processing pass, which runs after the screen shader at the window's
+    /// resolution. Passes run in the order they are added. Each pass is compiled when the window
+    /// is built, and `build()` returns an error if any of them is invalid.
+    #[must_use]
+    pub fn with_post_process(mut self, pass: render::PostProcessPass) -> Self {
+        self.post_process.push(pass);
+
+        self
+    }
+
+    /// Add one of the built-in effects as a post-processing pass. Effects and passes run in the
+    /// order they are added, so e.g. `Effect::Curvature` usually goes last.
+    #[must_use]
+    pub fn with_effect(self, effect: render::Effect) -> Self {
+        self.with_post_process(effect.into())
+    }
+
     /// Build the window.
     pub fn build(self) -> Result<Window, WindowError> {
         // TODO: Don't create a window bigger than the display.
@@ -167,7 +271,7 @@ impl<'a> WindowBuilder<'a> {
             .with_min_inner_size(winit::dpi::PhysicalSize::new(1, 1))
             .build(&event_loop)?;
 
-        let renderer = crate::graphics::render::Renderer::new(
+        let mut renderer = crate::graphics::render::Renderer::new(
             Some(&window),
             (self.width as _, self.height as _),
             self.sprite_texture,
@@ -178,7 +282,18 @@ impl<'a> WindowBuilder<'a> {
             } else {
                 wgpu::PresentMode::Mailbox
             },
+            false,
         )?;
+        renderer.scaling_mode = self.scaling_mode;
+        if let Some(source) = self.cell_shader {
+            renderer.set_cell_shader(source)?;
+        }
+        if let Some(source) = self.screen_shader {
+            renderer.set_screen_shader(source)?;
+        }
+        for pass in self.post_process.iter() {
+            renderer.add_post_process(pass)?;
+        }
 
         Ok(Window {
             width: self.width,
@@ -186,6 +301,7 @@ impl<'a> WindowBuilder<'a> {
             window,
             event_loop,
             renderer,
+            resizable_grid: self.resizable_grid,
         })
     }
 }
@@ -200,6 +316,9 @@ pub struct Window {
     // Width & height of the window (in sprites).
     pub(crate) width: u32,
     pub(crate) height: u32,
+
+    // Whether the grid is resized to fill the window.
+    pub(crate) resizable_grid: bool,
 }
 
 impl Window {